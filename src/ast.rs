@@ -0,0 +1,275 @@
+use std::fmt::{Display, Formatter, Result};
+use std::rc::Rc;
+use crate::bigint::BigInt;
+use crate::token::{Span, TokenType};
+
+/// Statement lists and subexpressions are shared, the evaluator holds on to the parts it has yet to run.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Program {
+    pub statements: Rc<[Statement]>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub struct BlockStatement {
+    pub statements: Rc<[Statement]>,
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Statement {
+    /// `span` is where the name is declared.
+    Variable { name: String, value: Rc<Expression>, span: Span },
+    Return { value: Rc<Expression>, span: Span },
+    Expression(Rc<Expression>),
+    While { label: Option<String>, condition: Rc<Expression>, body: BlockStatement },
+    ForIn { label: Option<String>, variable: String, iterable: Rc<Expression>, body: BlockStatement },
+    For {
+        label: Option<String>,
+        init: Option<Box<Statement>>,
+        condition: Option<Rc<Expression>>,
+        step: Option<Rc<Expression>>,
+        body: BlockStatement,
+    },
+    Break(Option<String>),
+    Continue(Option<String>),
+    /// At least one of `catch` and `finally` is present.
+    Try { body: BlockStatement, catch: Option<(String, BlockStatement)>, finally: Option<BlockStatement> },
+    Throw { value: Rc<Expression>, span: Span },
+    /// `import "path"` binds the exports of the module at `path` to `name`, its file name without the
+    /// extension. `span` is where the path is.
+    Import { path: String, name: String, span: Span },
+    /// `export var ...`, always holds a `Variable`.
+    Export(Box<Statement>),
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum Expression {
+    Identifier { name: String, span: Span },
+    Integer(i64),
+    BigInteger(BigInt),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    /// A string with embedded expressions, `"a ${b} c"`.
+    Interpolated(Vec<StringPart>),
+    Null,
+    Prefix { operator: TokenType, right: Rc<Expression>, span: Span },
+    Infix { operator: TokenType, left: Rc<Expression>, right: Rc<Expression>, span: Span },
+    Assign { name: String, value: Rc<Expression>, span: Span },
+    If { condition: Rc<Expression>, consequence: BlockStatement, alternative: Option<BlockStatement> },
+    /// Functions declared with `var name = func...` are named after their variable.
+    Function { name: Option<String>, parameters: Vec<String>, parameter_spans: Vec<Span>, body: BlockStatement },
+    Call { function: Rc<Expression>, arguments: Vec<Rc<Expression>>, span: Span },
+    Array(Vec<Rc<Expression>>),
+    Hash(Vec<(Rc<Expression>, Rc<Expression>)>),
+    Index { left: Rc<Expression>, index: Rc<Expression>, span: Span },
+    Slice { left: Rc<Expression>, start: Option<Rc<Expression>>, end: Option<Rc<Expression>>, span: Span },
+    Member { object: Rc<Expression>, property: String, optional: bool, span: Span },
+    /// The postfix `value?` unwrapping an `Ok` or returning an `Err` from the enclosing function.
+    Propagate { value: Rc<Expression>, span: Span },
+}
+
+#[derive(PartialEq, Debug, Clone)]
+pub enum StringPart {
+    Literal(String),
+    Expression { expression: Rc<Expression>, format: Option<FormatSpec> },
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+/// The `[alignment][0][width][.precision]` after a colon in an interpolation, as in `${value:>8.2}`.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct FormatSpec {
+    pub alignment: Option<Alignment>,
+    pub zero_pad: bool,
+    pub width: Option<usize>,
+    pub precision: Option<usize>,
+}
+
+impl Expression {
+    /// Where the operation an error can be blamed on appears in the source: the operator, the
+    /// opening parenthesis of a call or the identifier. Literals and compound expressions have none.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Expression::Identifier { span, .. }
+            | Expression::Prefix { span, .. }
+            | Expression::Infix { span, .. }
+            | Expression::Assign { span, .. }
+            | Expression::Call { span, .. }
+            | Expression::Index { span, .. }
+            | Expression::Slice { span, .. }
+            | Expression::Member { span, .. }
+            | Expression::Propagate { span, .. } => Some(*span),
+            _ => None,
+        }
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        for statement in self.statements.iter() {
+            write!(f, "{statement}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for BlockStatement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{{ ")?;
+        for statement in self.statements.iter() {
+            write!(f, "{statement} ")?;
+        }
+        write!(f, "}}")
+    }
+}
+
+fn join(expressions: &[Rc<Expression>]) -> String {
+    let expressions: Vec<String> = expressions.iter().map(|expression| expression.to_string()).collect();
+    expressions.join(", ")
+}
+
+fn write_label(f: &mut Formatter<'_>, label: &Option<String>) -> Result {
+    match label {
+        Some(label) => write!(f, "{label}: "),
+        None => Ok(()),
+    }
+}
+
+impl Display for Statement {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Statement::Variable { name, value, .. } => write!(f, "var {name} = {value};"),
+            Statement::Return { value, .. } => write!(f, "return {value};"),
+            Statement::Expression(expression) => write!(f, "{expression}"),
+            Statement::While { label, condition, body } => {
+                write_label(f, label)?;
+                write!(f, "while ({condition}) {body}")
+            }
+            Statement::ForIn { label, variable, iterable, body } => {
+                write_label(f, label)?;
+                write!(f, "for ({variable} in {iterable}) {body}")
+            }
+            Statement::For { label, init, condition, step, body } => {
+                write_label(f, label)?;
+                write!(f, "for (")?;
+                match init {
+                    Some(init) => write!(f, "{init}")?,
+                    None => write!(f, ";")?,
+                }
+                if let Some(condition) = condition {
+                    write!(f, " {condition}")?;
+                }
+                write!(f, ";")?;
+                if let Some(step) = step {
+                    write!(f, " {step}")?;
+                }
+                write!(f, ") {body}")
+            }
+            Statement::Break(Some(label)) => write!(f, "break {label};"),
+            Statement::Break(None) => write!(f, "break;"),
+            Statement::Continue(Some(label)) => write!(f, "continue {label};"),
+            Statement::Continue(None) => write!(f, "continue;"),
+            Statement::Try { body, catch, finally } => {
+                write!(f, "try {body}")?;
+                if let Some((name, catch)) = catch {
+                    write!(f, " catch ({name}) {catch}")?;
+                }
+                if let Some(finally) = finally {
+                    write!(f, " finally {finally}")?;
+                }
+                Ok(())
+            }
+            Statement::Throw { value, .. } => write!(f, "throw {value};"),
+            Statement::Import { path, .. } => write!(f, "import {path:?};"),
+            Statement::Export(statement) => write!(f, "export {statement}"),
+        }
+    }
+}
+
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Expression::Identifier { name, .. } => write!(f, "{name}"),
+            Expression::Integer(value) => write!(f, "{value}"),
+            Expression::BigInteger(value) => write!(f, "{value}"),
+            Expression::Double(value) => write!(f, "{value:?}"),
+            Expression::Boolean(value) => write!(f, "{value}"),
+            Expression::String(value) => write!(f, "{value:?}"),
+            Expression::Interpolated(parts) => {
+                write!(f, "\"")?;
+                for part in parts {
+                    match part {
+                        StringPart::Literal(value) => {
+                            let quoted = format!("{value:?}");
+                            write!(f, "{}", quoted[1..quoted.len() - 1].replace('$', "\\$"))?;
+                        }
+                        StringPart::Expression { expression, format: None } => write!(f, "${{{expression}}}")?,
+                        StringPart::Expression { expression, format: Some(format) } => {
+                            write!(f, "${{{expression}:{format}}}")?
+                        }
+                    }
+                }
+                write!(f, "\"")
+            }
+            Expression::Null => write!(f, "null"),
+            Expression::Prefix { operator, right, .. } => write!(f, "({operator}{right})"),
+            Expression::Infix { operator, left, right, .. } => write!(f, "({left} {operator} {right})"),
+            Expression::Assign { name, value, .. } => write!(f, "({name} = {value})"),
+            Expression::If { condition, consequence, alternative } => {
+                write!(f, "if {condition} {consequence}")?;
+                if let Some(alternative) = alternative {
+                    write!(f, " else {alternative}")?;
+                }
+                Ok(())
+            }
+            Expression::Function { parameters, body, .. } => write!(f, "func({}) {body}", parameters.join(", ")),
+            Expression::Call { function, arguments, .. } => write!(f, "{function}({})", join(arguments)),
+            Expression::Array(elements) => write!(f, "[{}]", join(elements)),
+            Expression::Hash(pairs) => {
+                let pairs: Vec<String> = pairs.iter().map(|(key, value)| format!("{key}: {value}")).collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Expression::Index { left, index, .. } => write!(f, "({left}[{index}])"),
+            Expression::Slice { left, start, end, .. } => {
+                write!(f, "({left}[")?;
+                if let Some(start) = start {
+                    write!(f, "{start}")?;
+                }
+                write!(f, ":")?;
+                if let Some(end) = end {
+                    write!(f, "{end}")?;
+                }
+                write!(f, "])")
+            }
+            Expression::Member { object, property, optional: false, .. } => write!(f, "({object}.{property})"),
+            Expression::Member { object, property, optional: true, .. } => write!(f, "({object}?.{property})"),
+            Expression::Propagate { value, .. } => write!(f, "({value}?)"),
+        }
+    }
+}
+
+impl Display for FormatSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self.alignment {
+            Some(Alignment::Left) => write!(f, "<")?,
+            Some(Alignment::Center) => write!(f, "^")?,
+            Some(Alignment::Right) => write!(f, ">")?,
+            None => {}
+        }
+        if self.zero_pad {
+            write!(f, "0")?;
+        }
+        if let Some(width) = self.width {
+            write!(f, "{width}")?;
+        }
+        if let Some(precision) = self.precision {
+            write!(f, ".{precision}")?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,429 @@
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter, Result};
+use std::ops::{Add, Mul, Neg, Sub};
+
+/// Arbitrary precision integer, stored as a sign and a magnitude of base 2^32 digits, least
+/// significant digit first. The magnitude never ends with a zero digit and zero is never negative,
+/// so every value has exactly one representation and the derived equality and hash are exact.
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub struct BigInt {
+    negative: bool,
+    magnitude: Vec<u32>,
+}
+
+impl BigInt {
+    fn new(negative: bool, mut magnitude: Vec<u32>) -> BigInt {
+        trim(&mut magnitude);
+        let negative = negative && !magnitude.is_empty();
+        BigInt { negative, magnitude }
+    }
+
+    /// Parses an optionally negative string of decimal digits.
+    pub fn parse(literal: &str) -> Option<BigInt> {
+        let (negative, digits) = match literal.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, literal),
+        };
+        if digits.is_empty() || !digits.bytes().all(|digit| digit.is_ascii_digit()) {
+            return None;
+        }
+        let mut magnitude = vec![];
+        // up to 9 decimal digits fit in one u32 digit
+        for chunk in digits.as_bytes().chunks(9) {
+            let value = chunk.iter().fold(0, |value, digit| value * 10 + (digit - b'0') as u32);
+            mul_small_add(&mut magnitude, 10u32.pow(chunk.len() as u32), value);
+        }
+        Some(BigInt::new(negative, magnitude))
+    }
+
+    /// Converts the integral part of a finite double exactly.
+    pub fn from_f64(value: f64) -> Option<BigInt> {
+        if !value.is_finite() {
+            return None;
+        }
+        let bits = value.trunc().abs().to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        if exponent == 0 {
+            return Some(BigInt::new(false, vec![]));
+        }
+        // value = mantissa * 2^(exponent - 1075)
+        let mantissa = (bits & ((1 << 52) - 1)) | (1 << 52);
+        let shift = exponent - 1075;
+        let magnitude = if shift >= 0 {
+            shift_left(&[mantissa as u32, (mantissa >> 32) as u32], shift as usize)
+        } else {
+            let mantissa = mantissa >> -shift;
+            vec![mantissa as u32, (mantissa >> 32) as u32]
+        };
+        Some(BigInt::new(value < 0.0, magnitude))
+    }
+
+    pub fn to_i64(&self) -> Option<i64> {
+        if self.magnitude.len() > 2 {
+            return None;
+        }
+        let value = self.magnitude.iter().enumerate()
+            .fold(0u64, |value, (position, &digit)| value | (digit as u64) << (32 * position));
+        if self.negative {
+            // -2^63 is the only value whose magnitude does not fit in a positive i64
+            (value <= 1 << 63).then(|| (value as i64).wrapping_neg())
+        } else {
+            i64::try_from(value).ok()
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let value = self.magnitude.iter().rev().fold(0.0, |value, &digit| value * 4_294_967_296.0 + digit as f64);
+        if self.negative { -value } else { value }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.magnitude.is_empty()
+    }
+
+    /// Truncated division: the quotient is rounded toward zero and the remainder has the sign of
+    /// the dividend. Returns None for a zero divisor.
+    pub fn div_rem(&self, divisor: &BigInt) -> Option<(BigInt, BigInt)> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let (quotient, remainder) = div_rem_magnitude(&self.magnitude, &divisor.magnitude);
+        Some((BigInt::new(self.negative != divisor.negative, quotient), BigInt::new(self.negative, remainder)))
+    }
+}
+
+impl From<i64> for BigInt {
+    fn from(value: i64) -> Self {
+        let magnitude = value.unsigned_abs();
+        BigInt::new(value < 0, vec![magnitude as u32, (magnitude >> 32) as u32])
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => compare_magnitude(&self.magnitude, &other.magnitude),
+            (true, true) => compare_magnitude(&other.magnitude, &self.magnitude),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Neg for &BigInt {
+    type Output = BigInt;
+
+    fn neg(self) -> BigInt {
+        BigInt::new(!self.negative, self.magnitude.clone())
+    }
+}
+
+impl Add for &BigInt {
+    type Output = BigInt;
+
+    fn add(self, other: &BigInt) -> BigInt {
+        if self.negative == other.negative {
+            return BigInt::new(self.negative, add_magnitude(&self.magnitude, &other.magnitude));
+        }
+        match compare_magnitude(&self.magnitude, &other.magnitude) {
+            Ordering::Less => BigInt::new(other.negative, sub_magnitude(&other.magnitude, &self.magnitude)),
+            _ => BigInt::new(self.negative, sub_magnitude(&self.magnitude, &other.magnitude)),
+        }
+    }
+}
+
+impl Sub for &BigInt {
+    type Output = BigInt;
+
+    fn sub(self, other: &BigInt) -> BigInt {
+        self + &-other
+    }
+}
+
+impl Mul for &BigInt {
+    type Output = BigInt;
+
+    fn mul(self, other: &BigInt) -> BigInt {
+        BigInt::new(self.negative != other.negative, mul_magnitude(&self.magnitude, &other.magnitude))
+    }
+}
+
+impl Display for BigInt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        if self.is_zero() {
+            return write!(f, "0");
+        }
+        // peel off 9 decimal digits at a time, least significant first
+        let mut chunks = vec![];
+        let mut magnitude = self.magnitude.clone();
+        while !magnitude.is_empty() {
+            let (quotient, remainder) = div_rem_small(&magnitude, 1_000_000_000);
+            chunks.push(remainder);
+            magnitude = quotient;
+        }
+        if self.negative {
+            write!(f, "-")?;
+        }
+        let mut chunks = chunks.iter().rev();
+        if let Some(first) = chunks.next() {
+            write!(f, "{first}")?;
+        }
+        for chunk in chunks {
+            write!(f, "{chunk:09}")?;
+        }
+        Ok(())
+    }
+}
+
+fn trim(magnitude: &mut Vec<u32>) {
+    while magnitude.last() == Some(&0) {
+        magnitude.pop();
+    }
+}
+
+fn compare_magnitude(left: &[u32], right: &[u32]) -> Ordering {
+    left.len().cmp(&right.len()).then_with(|| left.iter().rev().cmp(right.iter().rev()))
+}
+
+fn add_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let (longer, shorter) = if left.len() >= right.len() { (left, right) } else { (right, left) };
+    let mut result = Vec::with_capacity(longer.len() + 1);
+    let mut carry = 0u64;
+    for (position, &digit) in longer.iter().enumerate() {
+        let sum = digit as u64 + *shorter.get(position).unwrap_or(&0) as u64 + carry;
+        result.push(sum as u32);
+        carry = sum >> 32;
+    }
+    if carry != 0 {
+        result.push(carry as u32);
+    }
+    result
+}
+
+/// Subtracts `right` from `left`, which must not be smaller.
+fn sub_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    let mut result = Vec::with_capacity(left.len());
+    let mut borrow = 0i64;
+    for (position, &digit) in left.iter().enumerate() {
+        let mut difference = digit as i64 - *right.get(position).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if difference < 0 {
+            difference += 1 << 32;
+            borrow = 1;
+        }
+        result.push(difference as u32);
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_magnitude(left: &[u32], right: &[u32]) -> Vec<u32> {
+    if left.is_empty() || right.is_empty() {
+        return vec![];
+    }
+    let mut result = vec![0u32; left.len() + right.len()];
+    for (i, &left_digit) in left.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, &right_digit) in right.iter().enumerate() {
+            let product = result[i + j] as u64 + left_digit as u64 * right_digit as u64 + carry;
+            result[i + j] = product as u32;
+            carry = product >> 32;
+        }
+        result[i + right.len()] = carry as u32;
+    }
+    trim(&mut result);
+    result
+}
+
+fn mul_small_add(magnitude: &mut Vec<u32>, factor: u32, addend: u32) {
+    let mut carry = addend as u64;
+    for digit in magnitude.iter_mut() {
+        let product = *digit as u64 * factor as u64 + carry;
+        *digit = product as u32;
+        carry = product >> 32;
+    }
+    if carry != 0 {
+        magnitude.push(carry as u32);
+    }
+}
+
+fn div_rem_small(magnitude: &[u32], divisor: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; magnitude.len()];
+    let mut remainder = 0u64;
+    for (position, &digit) in magnitude.iter().enumerate().rev() {
+        let current = remainder << 32 | digit as u64;
+        quotient[position] = (current / divisor as u64) as u32;
+        remainder = current % divisor as u64;
+    }
+    trim(&mut quotient);
+    (quotient, remainder as u32)
+}
+
+/// Schoolbook binary long division, the divisor must not be zero.
+fn div_rem_magnitude(dividend: &[u32], divisor: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if compare_magnitude(dividend, divisor) == Ordering::Less {
+        return (vec![], dividend.to_vec());
+    }
+    if divisor.len() == 1 {
+        let (quotient, remainder) = div_rem_small(dividend, divisor[0]);
+        return (quotient, if remainder == 0 { vec![] } else { vec![remainder] });
+    }
+
+    let mut quotient = vec![0u32; dividend.len()];
+    let mut remainder: Vec<u32> = vec![];
+    for bit in (0..dividend.len() * 32).rev() {
+        shift_in_bit(&mut remainder, (dividend[bit / 32] >> (bit % 32)) & 1);
+        if compare_magnitude(&remainder, divisor) != Ordering::Less {
+            remainder = sub_magnitude(&remainder, divisor);
+            quotient[bit / 32] |= 1 << (bit % 32);
+        }
+    }
+    trim(&mut quotient);
+    (quotient, remainder)
+}
+
+/// Shifts the magnitude one bit to the left and sets the lowest bit to `bit`.
+fn shift_in_bit(magnitude: &mut Vec<u32>, bit: u32) {
+    let mut carry = bit;
+    for digit in magnitude.iter_mut() {
+        let next_carry = *digit >> 31;
+        *digit = *digit << 1 | carry;
+        carry = next_carry;
+    }
+    if carry != 0 {
+        magnitude.push(carry);
+    }
+}
+
+fn shift_left(magnitude: &[u32], bits: usize) -> Vec<u32> {
+    let mut result = vec![0u32; bits / 32];
+    let offset = bits % 32;
+    let mut carry = 0u32;
+    for &digit in magnitude {
+        if offset == 0 {
+            result.push(digit);
+        } else {
+            result.push(digit << offset | carry);
+            carry = digit >> (32 - offset);
+        }
+    }
+    if carry != 0 {
+        result.push(carry);
+    }
+    result
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::cmp::Ordering;
+    use crate::bigint::BigInt;
+
+    fn big(literal: &str) -> BigInt {
+        BigInt::parse(literal).unwrap_or_else(|| panic!("invalid big integer {literal}"))
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        let tests: Vec<&str> = vec![
+            "0",
+            "1",
+            "-1",
+            "4294967295",
+            "4294967296",
+            "9223372036854775808",
+            "-9223372036854775809",
+            "1000000000000000000000000000000",
+            "123456789012345678901234567890123456789",
+            "-100000000000000000000000000000000000001",
+        ];
+
+        for literal in tests {
+            assert_eq!(literal, big(literal).to_string());
+        }
+        assert_eq!("0", big("-0").to_string());
+        assert_eq!("42", big("00042").to_string());
+        assert_eq!(None, BigInt::parse(""));
+        assert_eq!(None, BigInt::parse("-"));
+        assert_eq!(None, BigInt::parse("12a"));
+    }
+
+    #[test]
+    fn test_i64_conversions() {
+        for value in [0, 1, -1, i64::MAX, i64::MIN, i64::MIN + 1, 4294967296, -4294967296] {
+            assert_eq!(Some(value), BigInt::from(value).to_i64());
+            assert_eq!(value.to_string(), BigInt::from(value).to_string());
+        }
+        assert_eq!(None, big("9223372036854775808").to_i64());
+        assert_eq!(None, big("-9223372036854775809").to_i64());
+        assert_eq!(None, big("100000000000000000000000").to_i64());
+    }
+
+    #[test]
+    fn test_arithmetic_matches_i128() {
+        let values: Vec<i128> = vec![
+            0,
+            1,
+            -1,
+            7,
+            -13,
+            4294967295,
+            4294967296,
+            i64::MAX as i128,
+            i64::MIN as i128,
+            123456789012345678901234567,
+            -98765432109876543210987654,
+        ];
+
+        for &left in &values {
+            for &right in &values {
+                let big_left = big(&left.to_string());
+                let big_right = big(&right.to_string());
+                assert_eq!((left + right).to_string(), (&big_left + &big_right).to_string(), "{left} + {right}");
+                assert_eq!((left - right).to_string(), (&big_left - &big_right).to_string(), "{left} - {right}");
+                assert_eq!(left.cmp(&right), big_left.cmp(&big_right), "{left} cmp {right}");
+                if let Some(product) = left.checked_mul(right) {
+                    assert_eq!(product.to_string(), (&big_left * &big_right).to_string(), "{left} * {right}");
+                }
+                if right != 0 {
+                    let (quotient, remainder) = big_left.div_rem(&big_right).unwrap();
+                    assert_eq!((left / right).to_string(), quotient.to_string(), "{left} / {right}");
+                    assert_eq!((left % right).to_string(), remainder.to_string(), "{left} % {right}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_large_arithmetic() {
+        let factorial = (1..=30).fold(BigInt::from(1), |product, n| &product * &BigInt::from(n));
+        assert_eq!("265252859812191058636308480000000", factorial.to_string());
+
+        let (quotient, remainder) = factorial.div_rem(&big("1000000000000000000001")).unwrap();
+        assert_eq!("265252859812", quotient.to_string());
+        assert_eq!("191058636043227140188", remainder.to_string());
+        assert_eq!(None, factorial.div_rem(&BigInt::from(0)));
+    }
+
+    #[test]
+    fn test_double_conversions() {
+        assert_eq!(Some(big("9007199254740992")), BigInt::from_f64(9007199254740992.0));
+        assert_eq!(Some(big("-1")), BigInt::from_f64(-1.9));
+        assert_eq!(Some(big("0")), BigInt::from_f64(0.5));
+        assert_eq!(Some(big("0")), BigInt::from_f64(-0.0));
+        assert_eq!(Some(big("100000000000000000000")), BigInt::from_f64(1e20));
+        assert_eq!(None, BigInt::from_f64(f64::NAN));
+        assert_eq!(None, BigInt::from_f64(f64::INFINITY));
+
+        assert_eq!(1e20, big("100000000000000000000").to_f64());
+        assert_eq!(-9223372036854775808.0, BigInt::from(i64::MIN).to_f64());
+        assert_eq!(Ordering::Greater, big("100000000000000000001").cmp(&BigInt::from_f64(1e20).unwrap()));
+    }
+}
@@ -0,0 +1,1000 @@
+use crate::bigint::BigInt;
+use crate::evaluator::Evaluator;
+use crate::object::{Builtin, ErrorKind, Hash, Object, RuntimeError};
+
+/// Functions available to every script. They are only consulted when an identifier has no
+/// binding in scope, so scripts are free to shadow them.
+const BUILTINS: &[Builtin] = &[
+    Builtin { name: "print", function: print },
+    Builtin { name: "println", function: println },
+    Builtin { name: "len", function: len },
+    Builtin { name: "type_of", function: type_of },
+    Builtin { name: "first", function: first },
+    Builtin { name: "last", function: last },
+    Builtin { name: "rest", function: rest },
+    Builtin { name: "push", function: push },
+    Builtin { name: "range", function: range },
+    Builtin { name: "str", function: str },
+    Builtin { name: "int", function: int },
+    Builtin { name: "float", function: float },
+    Builtin { name: "split", function: split },
+    Builtin { name: "join", function: join },
+    Builtin { name: "trim", function: trim },
+    Builtin { name: "upper", function: upper },
+    Builtin { name: "lower", function: lower },
+    Builtin { name: "contains", function: contains },
+    Builtin { name: "replace", function: replace },
+    Builtin { name: "starts_with", function: starts_with },
+    Builtin { name: "ends_with", function: ends_with },
+    Builtin { name: "find", function: find },
+    Builtin { name: "repeat", function: repeat },
+    Builtin { name: "Ok", function: ok },
+    Builtin { name: "Err", function: err },
+    Builtin { name: "is_ok", function: is_ok },
+    Builtin { name: "is_err", function: is_err },
+    Builtin { name: "unwrap", function: unwrap },
+    Builtin { name: "unwrap_or", function: unwrap_or },
+    Builtin { name: "map_err", function: map_err },
+    Builtin { name: "gc", function: gc },
+    Builtin { name: "map", function: map },
+    Builtin { name: "filter", function: filter },
+    Builtin { name: "reduce", function: reduce },
+    Builtin { name: "any", function: any },
+    Builtin { name: "all", function: all },
+    Builtin { name: "sort", function: sort },
+    Builtin { name: "sort_by", function: sort_by },
+    Builtin { name: "group_by", function: group_by },
+    Builtin { name: "zip", function: zip },
+    Builtin { name: "enumerate", function: enumerate },
+];
+
+pub fn lookup(name: &str) -> Option<Object> {
+    BUILTINS.iter().find(|builtin| builtin.name == name).map(|builtin| Object::Builtin(builtin.clone()))
+}
+
+fn check_arity(name: &str, arguments: &[Object], min: usize, max: usize) -> Result<(), RuntimeError> {
+    if (min..=max).contains(&arguments.len()) {
+        return Ok(());
+    }
+    let expected = if min == max { min.to_string() } else { format!("{min} to {max}") };
+    Err(RuntimeError::new(ErrorKind::ArgumentError, format!(
+        "{name}: wrong number of arguments: expected {expected}, got {}", arguments.len()
+    )))
+}
+
+fn argument_error(name: &str, expected: &str, argument: &Object) -> RuntimeError {
+    RuntimeError::new(ErrorKind::TypeError, format!("{name}: argument must be {expected}, got {}", argument.type_name()))
+}
+
+fn write_arguments(evaluator: &mut Evaluator, name: &str, arguments: &[Object], end: &str) -> Result<Object, RuntimeError> {
+    evaluator.check_io(name)?;
+    let line: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+    write!(evaluator.output(), "{}{end}", line.join(" "))
+        .and_then(|_| evaluator.output().flush())
+        .map_err(|e| RuntimeError::new(ErrorKind::IoError, format!("{name}: {e}")))?;
+    Ok(Object::Null)
+}
+
+fn print(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    write_arguments(evaluator, "print", &arguments, "")
+}
+
+fn println(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    write_arguments(evaluator, "println", &arguments, "\n")
+}
+
+fn len(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("len", &arguments, 1, 1)?;
+    let length = match &arguments[0] {
+        Object::String(value) => value.chars().count(),
+        Object::Array(elements) => elements.borrow().len(),
+        Object::Hash(hash) => hash.borrow().len(),
+        other => return Err(argument_error("len", "STRING, ARRAY or HASH", other)),
+    };
+    Ok(Object::Integer(length as i64))
+}
+
+fn type_of(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("type_of", &arguments, 1, 1)?;
+    Ok(Object::String(arguments[0].type_name().to_lowercase()))
+}
+
+fn array_argument(name: &str, argument: &Object) -> Result<Vec<Object>, RuntimeError> {
+    match argument {
+        Object::Array(elements) => Ok(elements.borrow().clone()),
+        other => Err(argument_error(name, "ARRAY", other)),
+    }
+}
+
+fn first(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("first", &arguments, 1, 1)?;
+    let elements = array_argument("first", &arguments[0])?;
+    Ok(elements.first().cloned().unwrap_or(Object::Null))
+}
+
+fn last(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("last", &arguments, 1, 1)?;
+    let elements = array_argument("last", &arguments[0])?;
+    Ok(elements.last().cloned().unwrap_or(Object::Null))
+}
+
+/// Returns a new array without the first element, or null for an empty array.
+fn rest(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("rest", &arguments, 1, 1)?;
+    let elements = array_argument("rest", &arguments[0])?;
+    match elements.split_first() {
+        Some((_, rest)) => Ok(evaluator.heap().new_array(rest.to_vec())),
+        None => Ok(Object::Null),
+    }
+}
+
+/// Appends to the array in place and returns the same array.
+fn push(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("push", &arguments, 2, 2)?;
+    match &arguments[0] {
+        Object::Array(elements) => {
+            elements.borrow_mut().push(arguments[1].clone());
+            Ok(arguments[0].clone())
+        }
+        other => Err(argument_error("push", "ARRAY", other)),
+    }
+}
+
+/// `range(end)`, `range(start, end)` or `range(start, end, step)`, the end is excluded.
+fn range(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("range", &arguments, 1, 3)?;
+    let mut bounds = Vec::with_capacity(arguments.len());
+    for argument in &arguments {
+        match argument {
+            Object::Integer(value) => bounds.push(*value),
+            other => return Err(argument_error("range", "INTEGER", other)),
+        }
+    }
+    let (start, end, step) = match bounds[..] {
+        [end] => (0, end, 1),
+        [start, end] => (start, end, 1),
+        [start, end, step] => (start, end, step),
+        _ => unreachable!("the arity is checked above"),
+    };
+    if step == 0 {
+        return Err(RuntimeError::new(ErrorKind::ValueError, "range: step must not be zero".to_string()));
+    }
+    // rounded up, the last element may be less than a whole step from the end
+    let (span, step_size) = ((end as i128 - start as i128) * step.signum() as i128, (step as i128).abs());
+    let length = ((span + step_size - 1) / step_size).max(0) as usize;
+    evaluator.check_size(length.saturating_mul(size_of::<Object>()))?;
+
+    let mut elements = vec![];
+    let mut current = start;
+    while (step > 0 && current < end) || (step < 0 && current > end) {
+        elements.push(Object::Integer(current));
+        current = match current.checked_add(step) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(evaluator.heap().new_array(elements))
+}
+
+fn str(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("str", &arguments, 1, 1)?;
+    let text = arguments[0].to_string();
+    evaluator.check_size(text.len())?;
+    Ok(Object::String(text))
+}
+
+/// Converts to an integer, doubles are truncated toward zero.
+fn int(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("int", &arguments, 1, 1)?;
+    let conversion_error = || {
+        RuntimeError::new(ErrorKind::ValueError, format!("int: cannot convert {} to INTEGER", arguments[0].inspect()))
+    };
+    match &arguments[0] {
+        Object::Integer(_) | Object::BigInteger(_) => Ok(arguments[0].clone()),
+        Object::Double(value) => match BigInt::from_f64(*value) {
+            Some(value) => Ok(Object::from_big_integer(value)),
+            None => Err(conversion_error()),
+        },
+        Object::String(value) => match value.parse::<i64>() {
+            Ok(value) => Ok(Object::Integer(value)),
+            Err(_) => BigInt::parse(value).map(Object::from_big_integer).ok_or_else(conversion_error),
+        },
+        Object::Boolean(value) => Ok(Object::Integer(*value as i64)),
+        other => Err(argument_error("int", "INTEGER, DOUBLE, STRING or BOOLEAN", other)),
+    }
+}
+
+fn float(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("float", &arguments, 1, 1)?;
+    match &arguments[0] {
+        Object::Integer(value) => Ok(Object::Double(*value as f64)),
+        Object::BigInteger(value) => Ok(Object::Double(value.to_f64())),
+        Object::Double(_) => Ok(arguments[0].clone()),
+        Object::String(value) => match value.parse::<f64>() {
+            Ok(value) => Ok(Object::Double(value)),
+            Err(_) => Err(RuntimeError::new(
+                ErrorKind::ValueError, format!("float: cannot convert {} to DOUBLE", arguments[0].inspect())
+            )),
+        },
+        other => Err(argument_error("float", "INTEGER, DOUBLE or STRING", other)),
+    }
+}
+
+fn string_argument<'a>(name: &str, argument: &'a Object) -> Result<&'a str, RuntimeError> {
+    match argument {
+        Object::String(value) => Ok(value),
+        other => Err(argument_error(name, "STRING", other)),
+    }
+}
+
+/// Splits around every occurrence of the separator, an empty separator splits into characters.
+fn split(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("split", &arguments, 2, 2)?;
+    let value = string_argument("split", &arguments[0])?;
+    let separator = string_argument("split", &arguments[1])?;
+    let count = if separator.is_empty() { value.chars().count() } else { value.matches(separator).count() + 1 };
+    evaluator.check_size(count.saturating_mul(size_of::<Object>()).saturating_add(value.len()))?;
+    let parts: Vec<Object> = if separator.is_empty() {
+        value.chars().map(|c| Object::String(c.to_string())).collect()
+    } else {
+        value.split(separator).map(|part| Object::String(part.to_string())).collect()
+    };
+    Ok(evaluator.heap().new_array(parts))
+}
+
+fn join(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("join", &arguments, 2, 2)?;
+    let elements = array_argument("join", &arguments[0])?;
+    let separator = string_argument("join", &arguments[1])?;
+    let mut parts = Vec::with_capacity(elements.len());
+    for element in &elements {
+        match element {
+            Object::String(value) => parts.push(value.as_str()),
+            other => return Err(RuntimeError::new(
+                ErrorKind::TypeError, format!("join: elements must be STRING, got {}", other.type_name())
+            )),
+        }
+    }
+    let separators = separator.len().saturating_mul(parts.len().saturating_sub(1));
+    evaluator.check_size(parts.iter().map(|part| part.len()).fold(separators, usize::saturating_add))?;
+    Ok(Object::String(parts.join(separator)))
+}
+
+fn trim(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("trim", &arguments, 1, 1)?;
+    let trimmed = string_argument("trim", &arguments[0])?.trim();
+    evaluator.check_size(trimmed.len())?;
+    Ok(Object::String(trimmed.to_string()))
+}
+
+/// Changing the case may change the length, some characters map to several.
+fn upper(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("upper", &arguments, 1, 1)?;
+    let value = string_argument("upper", &arguments[0])?;
+    evaluator.check_size(value.chars().flat_map(char::to_uppercase).map(char::len_utf8).sum())?;
+    Ok(Object::String(value.to_uppercase()))
+}
+
+fn lower(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("lower", &arguments, 1, 1)?;
+    let value = string_argument("lower", &arguments[0])?;
+    evaluator.check_size(value.chars().flat_map(char::to_lowercase).map(char::len_utf8).sum())?;
+    Ok(Object::String(value.to_lowercase()))
+}
+
+fn contains(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("contains", &arguments, 2, 2)?;
+    let value = string_argument("contains", &arguments[0])?;
+    let pattern = string_argument("contains", &arguments[1])?;
+    Ok(Object::Boolean(value.contains(pattern)))
+}
+
+/// Replaces every occurrence of a non-empty pattern.
+fn replace(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("replace", &arguments, 3, 3)?;
+    let value = string_argument("replace", &arguments[0])?;
+    let pattern = string_argument("replace", &arguments[1])?;
+    let replacement = string_argument("replace", &arguments[2])?;
+    if pattern.is_empty() {
+        return Err(RuntimeError::new(ErrorKind::ValueError, "replace: pattern must not be empty".to_string()));
+    }
+    let count = value.matches(pattern).count();
+    evaluator.check_size((value.len() - count * pattern.len()).saturating_add(count.saturating_mul(replacement.len())))?;
+    Ok(Object::String(value.replace(pattern, replacement)))
+}
+
+fn starts_with(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("starts_with", &arguments, 2, 2)?;
+    let value = string_argument("starts_with", &arguments[0])?;
+    let prefix = string_argument("starts_with", &arguments[1])?;
+    Ok(Object::Boolean(value.starts_with(prefix)))
+}
+
+fn ends_with(_: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("ends_with", &arguments, 2, 2)?;
+    let value = string_argument("ends_with", &arguments[0])?;
+    let suffix = string_argument("ends_with", &arguments[1])?;
+    Ok(Object::Boolean(value.ends_with(suffix)))
+}
+
+/// Returns the character index of the first occurrence, matching string indexing, or null.
+/// `find(text, pattern)` gives the position of the pattern in the text, `find(items, predicate)` the
+/// first item the predicate holds for. Either gives null if there is none.
+fn find(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("find", &arguments, 2, 2)?;
+    if !matches!(arguments[0], Object::String(_)) {
+        return find_item(evaluator, arguments);
+    }
+    let value = string_argument("find", &arguments[0])?;
+    let pattern = string_argument("find", &arguments[1])?;
+    match value.find(pattern) {
+        Some(byte_index) => Ok(Object::Integer(value[..byte_index].chars().count() as i64)),
+        None => Ok(Object::Null),
+    }
+}
+
+fn repeat(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("repeat", &arguments, 2, 2)?;
+    let value = string_argument("repeat", &arguments[0])?;
+    let count = match &arguments[1] {
+        Object::Integer(count) if *count >= 0 => *count as usize,
+        Object::Integer(_) => {
+            return Err(RuntimeError::new(ErrorKind::ValueError, "repeat: count must not be negative".to_string()))
+        }
+        other => return Err(argument_error("repeat", "INTEGER", other)),
+    };
+    if value.len().checked_mul(count).is_none_or(|length| length > isize::MAX as usize) {
+        return Err(RuntimeError::new(ErrorKind::ValueError, "repeat: result is too long".to_string()));
+    }
+    evaluator.check_size(value.len() * count)?;
+    Ok(Object::String(value.repeat(count)))
+}
+
+fn ok(_: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("Ok", &arguments, 1, 1)?;
+    Ok(Object::Ok(Box::new(arguments.remove(0))))
+}
+
+fn err(_: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("Err", &arguments, 1, 1)?;
+    Ok(Object::Err(Box::new(arguments.remove(0))))
+}
+
+fn result_argument(name: &str, argument: Object) -> Result<Result<Object, Object>, RuntimeError> {
+    match argument {
+        Object::Ok(value) => Ok(Ok(*value)),
+        Object::Err(error) => Ok(Err(*error)),
+        other => Err(argument_error(name, "RESULT", &other)),
+    }
+}
+
+fn is_ok(_: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("is_ok", &arguments, 1, 1)?;
+    Ok(Object::Boolean(result_argument("is_ok", arguments.remove(0))?.is_ok()))
+}
+
+fn is_err(_: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("is_err", &arguments, 1, 1)?;
+    Ok(Object::Boolean(result_argument("is_err", arguments.remove(0))?.is_err()))
+}
+
+fn unwrap(_: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("unwrap", &arguments, 1, 1)?;
+    match result_argument("unwrap", arguments.remove(0))? {
+        Ok(value) => Ok(value),
+        Err(error) => {
+            Err(RuntimeError::new(ErrorKind::ValueError, format!("unwrap: called on Err({})", error.inspect())))
+        }
+    }
+}
+
+fn unwrap_or(_: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("unwrap_or", &arguments, 2, 2)?;
+    let default = arguments.pop().unwrap();
+    Ok(result_argument("unwrap_or", arguments.remove(0))?.unwrap_or(default))
+}
+
+/// Passes the error of an `Err` through the function, an `Ok` is returned as it is.
+fn map_err(evaluator: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("map_err", &arguments, 2, 2)?;
+    let function = arguments.pop().unwrap();
+    match result_argument("map_err", arguments.remove(0))? {
+        Ok(value) => Ok(Object::Ok(Box::new(value))),
+        Err(error) => Ok(Object::Err(Box::new(evaluator.call(function, vec![error])?))),
+    }
+}
+
+/// Collects garbage right away, returns the collector statistics.
+fn gc(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("gc", &arguments, 0, 0)?;
+    evaluator.heap().collect();
+    let stats = evaluator.gc_stats().clone();
+    let mut hash = Hash::new();
+    let fields = [
+        ("collections", Object::Integer(stats.collections as i64)),
+        ("objects_freed", Object::Integer(stats.objects_freed as i64)),
+        ("bytes_freed", Object::Integer(stats.bytes_freed as i64)),
+        ("pause_ms", Object::Double(stats.pause_time.as_secs_f64() * 1000.0)),
+        ("live_objects", Object::Integer(stats.live_objects as i64)),
+    ];
+    for (key, value) in fields {
+        hash.insert(Object::String(key.to_string()), value)?;
+    }
+    Ok(evaluator.heap().new_hash(hash))
+}
+
+/// The items of a collection given to the functions below, copied first so that callbacks may change the
+/// collection. Callbacks get an array element, or the key and the value of a hash entry.
+struct Items {
+    hash: bool,
+    items: Vec<Vec<Object>>,
+}
+
+impl Items {
+    fn of(name: &str, value: &Object) -> Result<Items, RuntimeError> {
+        match value {
+            Object::Array(elements) => {
+                Ok(Items { hash: false, items: elements.borrow().iter().map(|element| vec![element.clone()]).collect() })
+            }
+            Object::Hash(hash) => {
+                let entries = hash.borrow().entries().iter().map(|(key, value)| vec![key.clone(), value.clone()]).collect();
+                Ok(Items { hash: true, items: entries })
+            }
+            other => Err(argument_error(name, "ARRAY or HASH", other)),
+        }
+    }
+
+    /// A collection of the same kind holding `items`.
+    fn collect(&self, evaluator: &mut Evaluator, items: Vec<Vec<Object>>) -> Result<Object, RuntimeError> {
+        if !self.hash {
+            return Ok(evaluator.heap().new_array(items.into_iter().map(|mut item| item.remove(0)).collect()));
+        }
+        let mut hash = Hash::new();
+        for item in items {
+            let [key, value] = <[Object; 2]>::try_from(item).expect("hash items are entries");
+            hash.insert(key, value)?;
+        }
+        Ok(evaluator.heap().new_hash(hash))
+    }
+
+    /// An item as a single value: the element, or the `[key, value]` array of an entry.
+    fn value(evaluator: &mut Evaluator, mut item: Vec<Object>) -> Object {
+        match item.len() {
+            1 => item.remove(0),
+            _ => evaluator.heap().new_array(item),
+        }
+    }
+}
+
+fn function_argument(name: &str, argument: &Object) -> Result<Object, RuntimeError> {
+    match argument {
+        Object::Function(_) | Object::Builtin(_) | Object::Native(_) => Ok(argument.clone()),
+        other => Err(argument_error(name, "FUNCTION", other)),
+    }
+}
+
+/// The items of the first argument and the callback given as the second one.
+fn items_and_function(name: &str, arguments: &[Object], max: usize) -> Result<(Items, Object), RuntimeError> {
+    check_arity(name, arguments, 2, max)?;
+    Ok((Items::of(name, &arguments[0])?, function_argument(name, &arguments[1])?))
+}
+
+/// The items for which `predicate` holds, up to `limit` of them.
+fn matching(
+    evaluator: &mut Evaluator, items: Vec<Vec<Object>>, predicate: &Object, limit: usize,
+) -> Result<Vec<Vec<Object>>, RuntimeError> {
+    let mut found = vec![];
+    for item in items {
+        let result = evaluator.call(predicate.clone(), item.clone())?;
+        if evaluator.is_truthy(&result)? {
+            found.push(item);
+            if found.len() == limit {
+                break;
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Calls the function on every item, a hash gives a hash with the same keys.
+fn map(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (items, function) = items_and_function("map", &arguments, 2)?;
+    let mut results = Vec::with_capacity(items.items.len());
+    for item in &items.items {
+        let result = evaluator.call(function.clone(), item.clone())?;
+        results.push(if items.hash { vec![item[0].clone(), result] } else { vec![result] });
+    }
+    items.collect(evaluator, results)
+}
+
+fn filter(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (mut items, predicate) = items_and_function("filter", &arguments, 2)?;
+    let kept = matching(evaluator, std::mem::take(&mut items.items), &predicate, usize::MAX)?;
+    items.collect(evaluator, kept)
+}
+
+/// `reduce(items, f, initial)` folds the items from the first, `f` gets the result so far followed by the item.
+fn reduce(evaluator: &mut Evaluator, mut arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("reduce", &arguments, 3, 3)?;
+    let (items, function) = items_and_function("reduce", &arguments[..2], 2)?;
+    let mut result = arguments.pop().unwrap();
+    for item in items.items {
+        let mut call_arguments = vec![result];
+        call_arguments.extend(item);
+        result = evaluator.call(function.clone(), call_arguments)?;
+    }
+    Ok(result)
+}
+
+fn any(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (items, predicate) = items_and_function("any", &arguments, 2)?;
+    Ok(Object::Boolean(!matching(evaluator, items.items, &predicate, 1)?.is_empty()))
+}
+
+fn all(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (items, predicate) = items_and_function("all", &arguments, 2)?;
+    for item in items.items {
+        let result = evaluator.call(predicate.clone(), item)?;
+        if !evaluator.is_truthy(&result)? {
+            return Ok(Object::Boolean(false));
+        }
+    }
+    Ok(Object::Boolean(true))
+}
+
+/// The first element, or `[key, value]` entry, the predicate holds for.
+fn find_item(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let items = match &arguments[0] {
+        Object::Array(_) | Object::Hash(_) => Items::of("find", &arguments[0])?,
+        other => return Err(argument_error("find", "STRING, ARRAY or HASH", other)),
+    };
+    let predicate = function_argument("find", &arguments[1])?;
+    match matching(evaluator, items.items, &predicate, 1)?.pop() {
+        Some(item) => Ok(Items::value(evaluator, item)),
+        None => Ok(Object::Null),
+    }
+}
+
+/// Stable merge sort of items by their keys, ordered like `<` orders them. Unordered keys, like NaN,
+/// keep their place relative to each other.
+fn sort_items(
+    evaluator: &Evaluator, items: Vec<(Object, Vec<Object>)>,
+) -> Result<Vec<(Object, Vec<Object>)>, RuntimeError> {
+    if items.len() < 2 {
+        return Ok(items);
+    }
+    let mut left = items;
+    let right = left.split_off(left.len() / 2);
+    let left = sort_items(evaluator, left)?;
+    let right = sort_items(evaluator, right)?;
+    let mut sorted = Vec::with_capacity(left.len() + right.len());
+    let mut left = left.into_iter().peekable();
+    let mut right = right.into_iter().peekable();
+    while let (Some(first), Some(second)) = (left.peek(), right.peek()) {
+        // taking from the left unless the right is smaller keeps equal items in order
+        let item = if evaluator.less_than(&second.0, &first.0)? { right.next() } else { left.next() };
+        sorted.extend(item);
+    }
+    sorted.extend(left);
+    sorted.extend(right);
+    Ok(sorted)
+}
+
+/// Sorts array elements, or hash entries by their keys.
+fn sort(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("sort", &arguments, 1, 1)?;
+    let items = Items::of("sort", &arguments[0])?;
+    let keyed = items.items.iter().map(|item| (item[0].clone(), item.clone())).collect();
+    let sorted = sort_items(evaluator, keyed)?.into_iter().map(|(_, item)| item).collect();
+    items.collect(evaluator, sorted)
+}
+
+/// Sorts by the keys the function gives, calling it once per item.
+fn sort_by(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (items, function) = items_and_function("sort_by", &arguments, 2)?;
+    let mut keyed = Vec::with_capacity(items.items.len());
+    for item in &items.items {
+        keyed.push((evaluator.call(function.clone(), item.clone())?, item.clone()));
+    }
+    let sorted = sort_items(evaluator, keyed)?.into_iter().map(|(_, item)| item).collect();
+    items.collect(evaluator, sorted)
+}
+
+/// A hash from the keys the function gives to the collections of items with that key, in the order
+/// the keys first appear.
+fn group_by(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    let (items, function) = items_and_function("group_by", &arguments, 2)?;
+    let mut positions = Hash::new();
+    let mut groups: Vec<(Object, Vec<Vec<Object>>)> = vec![];
+    for item in &items.items {
+        let key = evaluator.call(function.clone(), item.clone())?;
+        match positions.get(&key)? {
+            Some(Object::Integer(position)) => groups[position as usize].1.push(item.clone()),
+            _ => {
+                positions.insert(key.clone(), Object::Integer(groups.len() as i64))?;
+                groups.push((key, vec![item.clone()]));
+            }
+        }
+    }
+    let mut hash = Hash::new();
+    for (key, group) in groups {
+        let group = items.collect(evaluator, group)?;
+        hash.insert(key, group)?;
+    }
+    Ok(evaluator.heap().new_hash(hash))
+}
+
+/// Pairs the items at the same position, as long as both collections have one. Hash items are
+/// `[key, value]` arrays.
+fn zip(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("zip", &arguments, 2, 2)?;
+    let left = Items::of("zip", &arguments[0])?;
+    let right = Items::of("zip", &arguments[1])?;
+    let mut pairs = vec![];
+    for (left, right) in left.items.into_iter().zip(right.items) {
+        let pair = vec![Items::value(evaluator, left), Items::value(evaluator, right)];
+        pairs.push(evaluator.heap().new_array(pair));
+    }
+    Ok(evaluator.heap().new_array(pairs))
+}
+
+/// `[position, item]` arrays, hash items are `[key, value]` arrays.
+fn enumerate(evaluator: &mut Evaluator, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+    check_arity("enumerate", &arguments, 1, 1)?;
+    let items = Items::of("enumerate", &arguments[0])?;
+    let mut pairs = vec![];
+    for (position, item) in items.items.into_iter().enumerate() {
+        let pair = vec![Object::Integer(position as i64), Items::value(evaluator, item)];
+        pairs.push(evaluator.heap().new_array(pair));
+    }
+    Ok(evaluator.heap().new_array(pairs))
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+    use std::io::Write;
+    use std::rc::Rc;
+    use crate::evaluator::Evaluator;
+    use crate::lexer::Lexer;
+    use crate::object::{Object, RuntimeError};
+    use crate::parser::Parser;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn eval_with_output(input: &str) -> (Result<Object, RuntimeError>, String) {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "parser has errors: {:?}", parser.errors());
+        let output = SharedBuffer::default();
+        let mut evaluator = Evaluator::new();
+        evaluator.set_output(Box::new(output.clone()));
+        let result = evaluator.eval(&program);
+        let output = String::from_utf8(output.0.borrow().clone()).expect("output is not UTF-8");
+        (result, output)
+    }
+
+    fn assert_evaluates_to(tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match eval_with_output(input).0 {
+                Ok(value) => assert_eq!(expected, value.inspect(), "wrong value for {input}"),
+                Err(e) => panic!("unexpected error for {input}: {e}"),
+            }
+        }
+    }
+
+    fn assert_fails_with(tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match eval_with_output(input).0 {
+                Ok(value) => panic!("expected an error for {input}, got {value}"),
+                Err(e) => assert_eq!(expected, e.message, "wrong error for {input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_print() {
+        let tests: Vec<(&str, &str)> = vec![
+            (r#"print("a", 1, 2.5, true, null)"#, "a 1 2.5 true null"),
+            (r#"println("a"); println(); println([1, "b"], {"c": "d"})"#, "a\n\n[1, \"b\"] {\"c\": \"d\"}\n"),
+            ("print(); print()", ""),
+            ("for (x in [1, 2, 3]) { print(x); }", "123"),
+        ];
+
+        for (input, expected) in tests {
+            let (result, output) = eval_with_output(input);
+            assert_eq!("null", result.expect("print failed").to_string());
+            assert_eq!(expected, output, "wrong output for {input}");
+        }
+    }
+
+    #[test]
+    fn test_len_and_type_of() {
+        assert_evaluates_to(vec![
+            (r#"len("")"#, "0"),
+            (r#"len("four")"#, "4"),
+            (r#"len("héllo")"#, "5"),
+            ("len([1, 2, 3])", "3"),
+            (r#"len({"a": 1})"#, "1"),
+            ("type_of(1)", r#""integer""#),
+            ("type_of(100000000000000000000)", r#""integer""#),
+            ("type_of(1.5)", r#""double""#),
+            (r#"type_of("s")"#, r#""string""#),
+            ("type_of(true)", r#""boolean""#),
+            ("type_of(null)", r#""null""#),
+            ("type_of([])", r#""array""#),
+            ("type_of({})", r#""hash""#),
+            ("type_of(func() { })", r#""function""#),
+            ("type_of(len)", r#""builtin""#),
+        ]);
+        assert_fails_with(vec![
+            ("len(1)", "len: argument must be STRING, ARRAY or HASH, got INTEGER"),
+            (r#"len("one", "two")"#, "len: wrong number of arguments: expected 1, got 2"),
+            ("type_of()", "type_of: wrong number of arguments: expected 1, got 0"),
+        ]);
+    }
+
+    #[test]
+    fn test_array_builtins() {
+        assert_evaluates_to(vec![
+            ("first([1, 2, 3])", "1"),
+            ("first([])", "null"),
+            ("last([1, 2, 3])", "3"),
+            ("last([])", "null"),
+            ("rest([1, 2, 3])", "[2, 3]"),
+            ("rest([1])", "[]"),
+            ("rest([])", "null"),
+            ("var a = [1, 2]; rest(a); a", "[1, 2]"),
+            ("push([], 1)", "[1]"),
+            ("var a = [1]; push(a, 2); push(a, 3); a", "[1, 2, 3]"),
+            ("var a = []; var b = a; push(a, 1); b", "[1]"),
+            ("var a = []; for (x in range(3)) { push(a, x * x); } a", "[0, 1, 4]"),
+        ]);
+        assert_fails_with(vec![
+            ("first(1)", "first: argument must be ARRAY, got INTEGER"),
+            (r#"last("abc")"#, "last: argument must be ARRAY, got STRING"),
+            ("rest({})", "rest: argument must be ARRAY, got HASH"),
+            ("push(1, 1)", "push: argument must be ARRAY, got INTEGER"),
+            ("push([])", "push: wrong number of arguments: expected 2, got 1"),
+        ]);
+    }
+
+    #[test]
+    fn test_range() {
+        assert_evaluates_to(vec![
+            ("range(0)", "[]"),
+            ("range(4)", "[0, 1, 2, 3]"),
+            ("range(2, 5)", "[2, 3, 4]"),
+            ("range(5, 2)", "[]"),
+            ("range(0, 10, 3)", "[0, 3, 6, 9]"),
+            ("range(5, 0, -2)", "[5, 3, 1]"),
+            ("range(-2)", "[]"),
+        ]);
+        assert_fails_with(vec![
+            ("range()", "range: wrong number of arguments: expected 1 to 3, got 0"),
+            ("range(1, 2, 3, 4)", "range: wrong number of arguments: expected 1 to 3, got 4"),
+            ("range(1.5)", "range: argument must be INTEGER, got DOUBLE"),
+            ("range(0, 5, 0)", "range: step must not be zero"),
+        ]);
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_evaluates_to(vec![
+            ("str(12)", r#""12""#),
+            ("str(1.5)", r#""1.5""#),
+            (r#"str("s")"#, r#""s""#),
+            ("str([1, \"a\"])", r#""[1, \"a\"]""#),
+            ("str(null)", r#""null""#),
+            ("int(7)", "7"),
+            ("int(7.9)", "7"),
+            ("int(-7.9)", "-7"),
+            ("int(100000000000000000000.0)", "100000000000000000000"),
+            (r#"int("42")"#, "42"),
+            (r#"int("-42")"#, "-42"),
+            (r#"int("123456789012345678901234567890")"#, "123456789012345678901234567890"),
+            ("int(true)", "1"),
+            ("float(2)", "2.0"),
+            ("float(2.5)", "2.5"),
+            (r#"float("2.25")"#, "2.25"),
+            (r#"float("7")"#, "7.0"),
+        ]);
+        assert_fails_with(vec![
+            (r#"int("abc")"#, r#"int: cannot convert "abc" to INTEGER"#),
+            (r#"int("1.5")"#, r#"int: cannot convert "1.5" to INTEGER"#),
+            ("int(0.0 / 0.0)", "int: cannot convert NaN to INTEGER"),
+            ("int(null)", "int: argument must be INTEGER, DOUBLE, STRING or BOOLEAN, got NULL"),
+            (r#"float("x")"#, r#"float: cannot convert "x" to DOUBLE"#),
+            ("float([])", "float: argument must be INTEGER, DOUBLE or STRING, got ARRAY"),
+            ("str()", "str: wrong number of arguments: expected 1, got 0"),
+        ]);
+    }
+
+    #[test]
+    fn test_string_builtins() {
+        assert_evaluates_to(vec![
+            (r#"split("a,b,,c", ",")"#, r#"["a", "b", "", "c"]"#),
+            (r#"split("", ",")"#, r#"[""]"#),
+            (r#"split("héé", "")"#, r#"["h", "é", "é"]"#),
+            (r#"split("a--b", "--")"#, r#"["a", "b"]"#),
+            (r#"join(["a", "b", "c"], ", ")"#, r#""a, b, c""#),
+            (r#"join([], "-")"#, r#""""#),
+            (r#"join(split("a b c", " "), "_")"#, r#""a_b_c""#),
+            (r#"trim("  padded \n\t")"#, r#""padded""#),
+            (r#"upper("straße")"#, r#""STRASSE""#),
+            (r#"lower("ÀBC")"#, r#""àbc""#),
+            (r#"contains("haystack", "st")"#, "true"),
+            (r#"contains("haystack", "")"#, "true"),
+            (r#"contains("haystack", "needle")"#, "false"),
+            (r#"replace("a.b.c", ".", "::")"#, r#""a::b::c""#),
+            (r#"replace("abc", "x", "y")"#, r#""abc""#),
+            (r#"starts_with("prefix", "pre")"#, "true"),
+            (r#"starts_with("prefix", "fix")"#, "false"),
+            (r#"ends_with("prefix", "fix")"#, "true"),
+            (r#"find("hello", "l")"#, "2"),
+            (r#"find("héllo", "l")"#, "2"),
+            (r#"find("日本語", "語")"#, "2"),
+            (r#"find("hello", "z")"#, "null"),
+            (r#"repeat("ab", 3)"#, r#""ababab""#),
+            (r#"repeat("ab", 0)"#, r#""""#),
+        ]);
+        assert_fails_with(vec![
+            (r#"split("a", 1)"#, "split: argument must be STRING, got INTEGER"),
+            (r#"join([1, 2], ",")"#, "join: elements must be STRING, got INTEGER"),
+            (r#"join("ab", ",")"#, "join: argument must be ARRAY, got STRING"),
+            ("upper(1)", "upper: argument must be STRING, got INTEGER"),
+            (r#"replace("a", "", "b")"#, "replace: pattern must not be empty"),
+            (r#"replace("a", "a")"#, "replace: wrong number of arguments: expected 3, got 2"),
+            (r#"repeat("a", -1)"#, "repeat: count must not be negative"),
+            (r#"repeat("a", 1.5)"#, "repeat: argument must be INTEGER, got DOUBLE"),
+            (r#"repeat("ab", 9223372036854775807)"#, "repeat: result is too long"),
+        ]);
+    }
+
+    #[test]
+    fn test_result_builtins() {
+        assert_evaluates_to(vec![
+            ("Ok(1)", "Ok(1)"),
+            (r#"Err("bad")"#, r#"Err("bad")"#),
+            ("type_of(Ok(1))", r#""result""#),
+            ("is_ok(Ok(1))", "true"),
+            ("is_ok(Err(1))", "false"),
+            ("is_err(Err(1))", "true"),
+            ("unwrap(Ok([1]))", "[1]"),
+            ("unwrap_or(Ok(1), 2)", "1"),
+            ("unwrap_or(Err(1), 2)", "2"),
+            (r#"map_err(Err("bad"), func(e) { "wrapped: " + e })"#, r#"Err("wrapped: bad")"#),
+            ("map_err(Ok(1), func(e) { 2 })", "Ok(1)"),
+            ("map_err(Err(1), str)", r#"Err("1")"#),
+        ]);
+        assert_fails_with(vec![
+            ("is_ok(1)", "is_ok: argument must be RESULT, got INTEGER"),
+            (r#"unwrap(Err("bad"))"#, r#"unwrap: called on Err("bad")"#),
+            ("unwrap_or(Ok(1))", "unwrap_or: wrong number of arguments: expected 2, got 1"),
+            ("map_err(Err(1), func(a, b) { a })", "wrong number of arguments: expected 2, got 1"),
+            ("map_err(Err(1), func(e) { e / 0 })", "division by zero"),
+        ]);
+    }
+
+    #[test]
+    fn test_collection_builtins() {
+        let people = r#"var people = [{"name": "ann", "age": 31}, {"name": "bob", "age": 25}, {"name": "cy", "age": 31}];"#;
+        let by_name = format!("{people} sort_by(people, func(p) {{ p.name }})");
+        let by_age = format!("{people} map(sort_by(people, func(p) {{ p.age }}), func(p) {{ p.name }})");
+        let grouped = format!("{people} map(group_by(people, func(p) {{ p.age }}), func(age, group) {{ len(group) }})");
+        assert_evaluates_to(vec![
+            ("map([1, 2, 3], func(x) { x * 2 })", "[2, 4, 6]"),
+            ("map([], func(x) { x })", "[]"),
+            (r#"map({"a": 1, "b": 2}, func(key, value) { key + str(value) })"#, r#"{"a": "a1", "b": "b2"}"#),
+            ("map([-1, 2], str)", r#"["-1", "2"]"#),
+            ("filter([1, 2, 3, 4], func(x) { x / 2 * 2 == x })", "[2, 4]"),
+            (r#"filter({"a": 1, "b": 2}, func(key, value) { value > 1 })"#, r#"{"b": 2}"#),
+            ("reduce([1, 2, 3], func(total, x) { total * 10 + x }, 0)", "123"),
+            ("reduce([], func(total, x) { total + x }, 7)", "7"),
+            (r#"reduce({"a": 1, "b": 2}, func(keys, key, value) { keys + key }, "")"#, r#""ab""#),
+            ("[any([1, 5], func(x) { x > 4 }), any([1, 2], func(x) { x > 4 }), any([], func(x) { true })]", "[true, false, false]"),
+            ("[all([5, 6], func(x) { x > 4 }), all([5, 1], func(x) { x > 4 }), all([], func(x) { false })]", "[true, false, true]"),
+            (r#"any({"a": 1}, func(key, value) { key == "a" })"#, "true"),
+            ("find([1, 4, 6], func(x) { x > 3 })", "4"),
+            ("find([1, 2], func(x) { x > 3 })", "null"),
+            (r#"find({"a": 1, "b": 2}, func(key, value) { value == 2 })"#, r#"["b", 2]"#),
+            (r#"find("haystack", "st")"#, "3"),
+            ("var calls = 0; find([1, 2, 3], func(x) { calls = calls + 1; x == 2 }); calls", "2"),
+            ("var items = [3, 1, 2]; [sort(items), items]", "[[1, 2, 3], [3, 1, 2]]"),
+            (r#"sort(["b", "c", "a"])"#, r#"["a", "b", "c"]"#),
+            ("sort([2.5, 1, 10000000000000000000000, -3])", "[-3, 1, 2.5, 10000000000000000000000]"),
+            ("sort([])", "[]"),
+            (r#"sort({"b": 1, "a": 2})"#, r#"{"a": 2, "b": 1}"#),
+            (&by_name, r#"[{"name": "ann", "age": 31}, {"name": "bob", "age": 25}, {"name": "cy", "age": 31}]"#),
+            (&by_age, r#"["bob", "ann", "cy"]"#),
+            (r#"sort_by({"a": 3, "b": 1, "c": 2}, func(key, value) { value })"#, r#"{"b": 1, "c": 2, "a": 3}"#),
+            ("var calls = 0; sort_by([3, 2, 1, 4], func(x) { calls = calls + 1; x }); calls", "4"),
+            (&grouped, "{31: 2, 25: 1}"),
+            ("group_by([1, 2, 3, 4, 5], func(x) { x / 2 * 2 == x })", "{false: [1, 3, 5], true: [2, 4]}"),
+            (r#"group_by({"a": 1, "b": 2, "c": 1}, func(key, value) { value })"#, r#"{1: {"a": 1, "c": 1}, 2: {"b": 2}}"#),
+            (r#"zip([1, 2, 3], ["a", "b"])"#, r#"[[1, "a"], [2, "b"]]"#),
+            (r#"zip({"a": 1}, [true])"#, r#"[[["a", 1], true]]"#),
+            (r#"enumerate(["a", "b"])"#, r#"[[0, "a"], [1, "b"]]"#),
+            (r#"enumerate({"k": "v"})"#, r#"[[0, ["k", "v"]]]"#),
+            ("var items = [1, 2]; map(items, func(x) { push(items, x); x }); len(items)", "4"),
+        ]);
+        assert_fails_with(vec![
+            ("map(1, str)", "map: argument must be ARRAY or HASH, got INTEGER"),
+            ("map([1], 1)", "map: argument must be FUNCTION, got INTEGER"),
+            ("filter([1])", "filter: wrong number of arguments: expected 2, got 1"),
+            ("reduce([1], func(a, b) { a })", "reduce: wrong number of arguments: expected 3, got 2"),
+            ("find(1, str)", "find: argument must be STRING, ARRAY or HASH, got INTEGER"),
+            (r#"find("a", str)"#, "find: argument must be STRING, got BUILTIN"),
+            ("map([1, 0], func(x) { 1 / x })", "division by zero"),
+            (r#"map({"a": 1}, func(value) { value })"#, "wrong number of arguments: expected 1, got 2"),
+            (r#"sort([1, "a"])"#, "type mismatch: STRING < INTEGER"),
+            ("sort_by([1, 2], func(x) { [x] })", "unknown operator: ARRAY < ARRAY"),
+            ("group_by([1], func(x) { [x] })", "unusable as hash key: ARRAY"),
+            ("zip([1], 2)", "zip: argument must be ARRAY or HASH, got INTEGER"),
+        ]);
+    }
+
+    #[test]
+    fn test_collection_callback_errors() {
+        let input = "var check = func(x) { if (x > 1) { throw \"too big\" } x };\nmap([1, 2], check)";
+        let e = eval_with_output(input).0.unwrap_err();
+        assert_eq!("Error: too big at 1:36\n    in check called at 2:4", e.traceback());
+        assert_evaluates_to(vec![
+            (r#"try { filter([1, 2], func(x) { throw "no" }) } catch (e) { e.message }"#, r#""no""#),
+            ("var seen = []; try { any([1, 2, 3], func(x) { push(seen, x); 1 / (x - 2) == 5 }) } catch (e) { seen }", "[1, 2]"),
+        ]);
+    }
+
+    #[test]
+    fn test_builtins_can_be_shadowed() {
+        assert_evaluates_to(vec![
+            ("var len = func(x) { 42 }; len([1])", "42"),
+            ("func() { var first = 5; first }()", "5"),
+            ("var f = len; f([1, 2])", "2"),
+            ("len", "builtin function len"),
+        ]);
+        assert_fails_with(vec![("undefined_builtin(1)", "identifier not found: undefined_builtin")]);
+    }
+
+    #[test]
+    fn test_self_referencing_collections_print() {
+        assert_evaluates_to(vec![
+            ("var a = [1]; push(a, a); a", "[1, [...]]"),
+            (r#"var h = {}; var a = [h]; var g = {"a": a}; push(a, g); a"#, r#"[{}, {"a": [...]}]"#),
+        ]);
+    }
+
+    #[test]
+    fn test_gc() {
+        assert_evaluates_to(vec![
+            (r#"gc()["collections"]"#, "1"),
+            (r#"var make = func() { var a = []; push(a, a); null }; make(); make(); gc()["objects_freed"]"#, "2"),
+            (r#"var f = func() { var g = func() { g }; null }; f(); gc()["objects_freed"]"#, "2"),
+            (r#"var h = {"a": []}; push(h["a"], h); gc()["objects_freed"]"#, "0"),
+            (r#"var keep = []; push(keep, keep); gc(); len(keep)"#, "1"),
+            (r#"len([[1, 2], gc()][0])"#, "2"),
+            (r#"var stats = gc(); [stats["bytes_freed"] >= 0, stats["pause_ms"] >= 0.0]"#, "[true, true]"),
+            (
+                r#"var n = 0;
+                while (n < 30000) { var f = func() { var g = func() { g }; null }; f(); n = n + 1 }
+                var stats = gc();
+                [stats["collections"] > 3, stats["live_objects"] < 100]"#,
+                "[true, true]",
+            ),
+        ]);
+        assert_fails_with(vec![("gc(1)", "gc: wrong number of arguments: expected 0, got 1")]);
+    }
+}
@@ -0,0 +1,75 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use crate::object::Object;
+
+pub type Env = Rc<RefCell<Environment>>;
+
+#[derive(Debug)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+    pub fn new() -> Env {
+        Rc::new(RefCell::new(Environment { store: HashMap::new(), outer: None }))
+    }
+
+    pub fn new_enclosed(outer: Env) -> Env {
+        Rc::new(RefCell::new(Environment { store: HashMap::new(), outer: Some(outer) }))
+    }
+
+    /// Creates a sibling scope holding copies of this scope's bindings, so that closures
+    /// created before the copy keep seeing the old values.
+    pub fn copy(&self) -> Env {
+        Rc::new(RefCell::new(Environment { store: self.store.clone(), outer: self.outer.clone() }))
+    }
+
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self.outer.as_ref().and_then(|outer| outer.borrow().get(name)),
+        }
+    }
+
+    /// Declares `name` in this scope, shadowing any binding of the enclosing scopes.
+    pub fn set(&mut self, name: &str, value: Object) {
+        self.store.insert(name.to_string(), value);
+    }
+
+    /// Removes the binding of `name` from this scope, uncovering the one of the enclosing scopes.
+    pub fn remove(&mut self, name: &str) -> Option<Object> {
+        self.store.remove(name)
+    }
+
+    /// The names bound in this scope, not in the enclosing ones.
+    pub fn names(&self) -> impl Iterator<Item = &String> {
+        self.store.keys()
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &Object> {
+        self.store.values()
+    }
+
+    pub fn outer(&self) -> Option<&Env> {
+        self.outer.as_ref()
+    }
+
+    /// Removes every binding and the link to the enclosing scope, returning them.
+    pub fn clear(&mut self) -> (Vec<Object>, Option<Env>) {
+        (self.store.drain().map(|(_, value)| value).collect(), self.outer.take())
+    }
+
+    /// Updates the nearest existing binding of `name`, returns false if there is none.
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        if let Some(slot) = self.store.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => false,
+        }
+    }
+}
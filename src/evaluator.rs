@@ -0,0 +1,2354 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crate::ast::{BlockStatement, Expression, Program, Statement, StringPart};
+use crate::bigint::BigInt;
+use crate::builtins;
+use crate::environment::Env;
+use crate::gc::{GcStats, Heap};
+use crate::module;
+use crate::object::{ErrorKind, Frame, Function, Hash, Object, RuntimeError};
+use crate::token::{Span, TokenType};
+
+/// Anything that stops the evaluation of a statement list early.
+#[derive(Debug)]
+enum Interrupt {
+    Return(Object),
+    Break(Option<String>),
+    Continue(Option<String>),
+    /// Boxed to keep results small.
+    Error(Box<RuntimeError>),
+    TailCall(Box<TailCall>),
+}
+
+/// A `return f(x)` in a function body, made by `apply_function` once the current call is left.
+#[derive(Debug)]
+struct TailCall {
+    function: Object,
+    arguments: Vec<Object>,
+    call_site: Span,
+}
+
+impl From<RuntimeError> for Interrupt {
+    fn from(error: RuntimeError) -> Self {
+        Interrupt::Error(Box::new(error))
+    }
+}
+
+type EvalResult = Result<Object, Interrupt>;
+
+fn error(kind: ErrorKind, message: String) -> Interrupt {
+    Interrupt::Error(Box::new(RuntimeError::new(kind, message)))
+}
+
+fn out_of_bounds<T: std::fmt::Display>(index: T, length: usize) -> Interrupt {
+    error(ErrorKind::IndexError, format!("index out of bounds: index {index}, length {length}"))
+}
+
+fn stray_jump(interrupt: Interrupt) -> RuntimeError {
+    match interrupt {
+        Interrupt::Break(None) => RuntimeError::new(ErrorKind::SyntaxError, "break outside of a loop".to_string()),
+        Interrupt::Continue(None) => RuntimeError::new(ErrorKind::SyntaxError, "continue outside of a loop".to_string()),
+        Interrupt::Break(Some(label)) | Interrupt::Continue(Some(label)) => {
+            RuntimeError::new(ErrorKind::SyntaxError, format!("unknown loop label '{label}'"))
+        }
+        Interrupt::Error(error) => *error,
+        Interrupt::Return(_) | Interrupt::TailCall(_) => unreachable!("return is handled by the caller"),
+    }
+}
+
+/// Blames an error that has no location yet on `span`. An error that passed through a call happened in
+/// there, it keeps its unknown location.
+fn with_span(interrupt: Interrupt, span: Span) -> Interrupt {
+    match interrupt {
+        Interrupt::Error(mut e) if e.span.is_none() && e.frames.is_empty() => {
+            e.span = Some(span);
+            Interrupt::Error(e)
+        }
+        other => other,
+    }
+}
+
+/// Blames an error that has no location yet on the span of the expression it left, if it has one.
+fn blame(interrupt: Interrupt, span: Option<Span>) -> Interrupt {
+    match interrupt {
+        Interrupt::Error(mut e) if e.span.is_none() => {
+            e.span = span;
+            Interrupt::Error(e)
+        }
+        other => other,
+    }
+}
+
+/// What is left to do in a run of the evaluator. Tasks wait in a `Machine` rather than on the native
+/// stack, so scripts nest blocks and calls as deep as `max_call_depth` allows.
+enum Task {
+    /// Pushes the value of the expression.
+    Eval(Rc<Expression>, Env),
+    /// Runs `statements[next..]`, the value of each replaces the value of the statement before.
+    Statements { statements: Rc<[Statement]>, next: usize, env: Env },
+    /// Pops the values of the operands of the expression and pushes its value, see `apply_expression`.
+    Apply(Rc<Expression>, Env),
+    /// Blames errors passing through on the span, for operands evaluated after the expression was applied.
+    Blame(Span),
+    /// Binds the value pushed to the variable, the declaration evaluates to null.
+    Declare(String, Env),
+    Return,
+    /// `return f(x)` with the function and its arguments pushed, see `leave_call`.
+    TailCall { arguments: usize, call_site: Span },
+    Throw(Span),
+    /// Drops the value of the initializer of a for loop, then starts it.
+    Init(Box<Loop>),
+    /// Pops the iterable of a for-in loop and starts it.
+    Iterate(Box<Loop>, Rc<Expression>),
+    /// Pops the condition of a loop, runs the body if it holds.
+    Test(Box<Loop>),
+    /// Drops the value of the step of a for loop, then tests its condition.
+    Step(Box<Loop>),
+    /// Waits for an iteration of the body, break and continue are handled here.
+    Loop(Box<Loop>),
+    Try(Box<Try>),
+    /// Waits for the body of a function, return and tail calls are handled here.
+    Call(Box<CallFrame>),
+}
+
+struct Loop {
+    label: Option<String>,
+    kind: LoopKind,
+    body: BlockStatement,
+    /// The scope the loop runs in, for a for loop the scope of its variables, copied for every iteration.
+    env: Env,
+    /// Values on the stack before the body, break and continue drop the ones pushed since.
+    height: usize,
+}
+
+enum LoopKind {
+    While { condition: Rc<Expression> },
+    ForIn { variable: String, items: std::vec::IntoIter<Object> },
+    For { condition: Option<Rc<Expression>>, step: Option<Rc<Expression>> },
+}
+
+impl Loop {
+    fn new(label: &Option<String>, kind: LoopKind, body: &BlockStatement, env: Env) -> Box<Loop> {
+        Box::new(Loop { label: label.clone(), kind, body: body.clone(), env, height: 0 })
+    }
+
+    fn condition(&self) -> Option<&Rc<Expression>> {
+        match &self.kind {
+            LoopKind::While { condition } => Some(condition),
+            LoopKind::For { condition, .. } => condition.as_ref(),
+            LoopKind::ForIn { .. } => None,
+        }
+    }
+
+    /// Whether a break or continue with `label` is meant for this loop.
+    fn is_target(&self, label: &Option<String>) -> bool {
+        label.is_none() || *label == self.label
+    }
+}
+
+struct Try {
+    catch: Option<(String, BlockStatement)>,
+    finally: Option<BlockStatement>,
+    env: Env,
+    stage: TryStage,
+    /// Values on the stack before the try, interrupts drop the ones pushed since.
+    height: usize,
+}
+
+enum TryStage {
+    Body,
+    Catch,
+    /// Holds the result of the body or catch block, given back once finally completes.
+    Finally(EvalResult),
+}
+
+struct CallFrame {
+    function: Rc<Function>,
+    call_site: Span,
+    /// The call expression, `None` for calls made by builtins and the host.
+    blame: Option<Span>,
+    /// Values on the stack before the call, return drops the ones pushed since.
+    height: usize,
+}
+
+/// The tasks of a run, and the values computed for the tasks waiting for them.
+#[derive(Default)]
+struct Machine {
+    tasks: Vec<Task>,
+    values: Vec<Object>,
+}
+
+impl Machine {
+    fn pop(&mut self) -> Object {
+        self.values.pop().expect("tasks only pop the values pushed for them")
+    }
+
+    /// Pops the last `count` values, in the order they were pushed.
+    fn pop_many(&mut self, count: usize) -> Vec<Object> {
+        self.values.split_off(self.values.len() - count)
+    }
+
+    /// Evaluates the expressions, the last one first: push them right to left to evaluate them left to right.
+    fn push_evals<'a>(&mut self, expressions: impl IntoIterator<Item = &'a Rc<Expression>>, env: &Env) {
+        for expression in expressions {
+            self.tasks.push(Task::Eval(expression.clone(), env.clone()));
+        }
+    }
+
+    /// Runs the statements, an empty list evaluates to null.
+    fn push_statements(&mut self, statements: &Rc<[Statement]>, env: Env) {
+        self.values.push(Object::Null);
+        if !statements.is_empty() {
+            self.tasks.push(Task::Statements { statements: statements.clone(), next: 0, env });
+        }
+    }
+}
+
+/// Decides which values count as true in conditions and for the `!` operator.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum Truthiness {
+    /// Only booleans are accepted, anything else is a runtime type error.
+    Strict,
+    /// `false`, `null`, `0`, `0.0`, `""` and empty arrays and hashes are false, everything else is true.
+    #[default]
+    Loose,
+}
+
+/// What happens when integer arithmetic does not fit in 64 bits.
+#[derive(PartialEq, Debug, Clone, Copy, Default)]
+pub enum IntegerOverflow {
+    /// The result is promoted to a big integer.
+    #[default]
+    Promote,
+    /// The result wraps around in two's complement.
+    Wrapping,
+}
+
+/// Settings fixed for the whole run of an evaluator.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Config {
+    pub truthiness: Truthiness,
+    pub overflow: IntegerOverflow,
+    /// Calls nested deeper than this fail with a `StackOverflow` error.
+    pub max_call_depth: usize,
+    /// Arrays, hashes, functions and scopes created between two garbage collections, see `Heap`.
+    pub gc_threshold: usize,
+    pub limits: Limits,
+    /// Whether an `Interpreter` loads the standard library, see `prelude`.
+    pub prelude: bool,
+}
+
+/// Restrictions for running untrusted scripts, nothing is restricted by default. Exceeding a limit fails
+/// the script with an error that `catch` cannot handle, so it always reaches the host.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct Limits {
+    /// Expressions and loop iterations evaluated by one call of `eval`.
+    pub max_steps: Option<u64>,
+    /// Estimated size of the live arrays, hashes, functions and scopes, with the strings they hold.
+    /// It is measured every few thousand steps, a single value larger than the limit fails right away.
+    pub max_heap_bytes: Option<usize>,
+    /// Wall-clock time of one call of `eval`, checked every few thousand steps.
+    pub timeout: Option<Duration>,
+    /// Makes the builtins that write output, and `import`, fail with a `PermissionError`.
+    pub disable_io: bool,
+}
+
+/// Steps between two checks of the time and heap limits.
+const LIMIT_CHECK_INTERVAL: u64 = 4096;
+
+/// How deep builtins calling back functions, like `map`, may nest. Every builtin calling back starts a
+/// new run of the evaluator on the native stack.
+const MAX_NESTED_RUNS: usize = 100;
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            truthiness: Truthiness::default(),
+            overflow: IntegerOverflow::default(),
+            max_call_depth: 200_000,
+            gc_threshold: 10_000,
+            limits: Limits::default(),
+            prelude: true,
+        }
+    }
+}
+
+pub struct Evaluator {
+    env: Env,
+    /// The globals defined by the host and the exports of the prelude, seen by the main program, which
+    /// encloses it, and by every module.
+    shared: Env,
+    /// The module or prelude file running, `None` for the main program.
+    file: Option<Rc<str>>,
+    config: Config,
+    heap: Heap,
+    output: Box<dyn Write>,
+    /// Where the builtin running now was called, functions it calls back report this call site.
+    builtin_call_site: Span,
+    call_depth: usize,
+    /// Runs nested in each other, see `MAX_NESTED_RUNS`.
+    runs: usize,
+    steps: u64,
+    /// Step at which the limits are checked next.
+    next_limit_check: u64,
+    deadline: Option<Instant>,
+    /// Exports of the modules imported so far, by file.
+    modules: HashMap<PathBuf, Object>,
+    /// The files being run, the main script first when it is a file, then the modules being imported.
+    loading: Vec<PathBuf>,
+}
+
+impl Default for Evaluator {
+    fn default() -> Self {
+        Evaluator::new()
+    }
+}
+
+impl Evaluator {
+    pub fn new() -> Evaluator {
+        Evaluator::with_config(Config::default())
+    }
+
+    pub fn with_config(config: Config) -> Evaluator {
+        let mut heap = Heap::new(config.gc_threshold);
+        let shared = heap.new_environment(None);
+        Evaluator {
+            env: heap.new_environment(Some(shared.clone())),
+            shared,
+            file: None,
+            config,
+            heap,
+            output: Box::new(std::io::stdout()),
+            builtin_call_site: Span::default(),
+            call_depth: 0,
+            runs: 0,
+            steps: 0,
+            next_limit_check: u64::MAX,
+            deadline: None,
+            modules: HashMap::new(),
+            loading: vec![],
+        }
+    }
+
+    /// Redirects what `print` and `println` write, stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.output = output;
+    }
+
+    pub fn gc_stats(&self) -> &GcStats {
+        self.heap.stats()
+    }
+
+    pub(crate) fn heap(&mut self) -> &mut Heap {
+        &mut self.heap
+    }
+
+    pub(crate) fn output(&mut self) -> &mut dyn Write {
+        self.output.as_mut()
+    }
+
+    pub(crate) fn set_limits(&mut self, limits: Limits) {
+        self.config.limits = limits;
+    }
+
+    /// Fails when the limits forbid the output builtin `name`.
+    pub(crate) fn check_io(&self, name: &str) -> Result<(), RuntimeError> {
+        if self.config.limits.disable_io {
+            return Err(RuntimeError::new(ErrorKind::PermissionError, format!("{name}: I/O is disabled")));
+        }
+        Ok(())
+    }
+
+    /// Fails when a value of `bytes` could never fit in the heap limit, for values built in a single step.
+    pub(crate) fn check_size(&self, bytes: usize) -> Result<(), RuntimeError> {
+        match self.config.limits.max_heap_bytes {
+            Some(max) if bytes > max => Err(self.heap_limit_error(max)),
+            _ => Ok(()),
+        }
+    }
+
+    fn heap_limit_error(&self, max: usize) -> RuntimeError {
+        RuntimeError::new(ErrorKind::MemoryLimitExceeded, format!("heap limit of {max} bytes exceeded"))
+    }
+
+    /// Starts counting calls, steps and time anew.
+    fn start_run(&mut self) {
+        self.call_depth = 0;
+        let limits = &self.config.limits;
+        self.steps = 0;
+        self.deadline = limits.timeout.map(|timeout| Instant::now() + timeout);
+        self.next_limit_check = if limits.timeout.is_some() || limits.max_heap_bytes.is_some() {
+            LIMIT_CHECK_INTERVAL.min(limits.max_steps.unwrap_or(u64::MAX))
+        } else {
+            limits.max_steps.unwrap_or(u64::MAX)
+        };
+    }
+
+    /// Counts a step of evaluation, checking the limits when it is time to.
+    fn step(&mut self) -> Result<(), Interrupt> {
+        self.steps += 1;
+        if self.steps > self.next_limit_check {
+            self.check_limits()?;
+        }
+        Ok(())
+    }
+
+    fn check_limits(&mut self) -> Result<(), RuntimeError> {
+        let limits = &self.config.limits;
+        if let Some(max) = limits.max_steps.filter(|&max| self.steps > max) {
+            return Err(RuntimeError::new(ErrorKind::StepLimitExceeded, format!("step limit of {max} exceeded")));
+        }
+        if let Some(timeout) = limits.timeout.filter(|_| self.deadline.is_some_and(|deadline| Instant::now() > deadline)) {
+            return Err(RuntimeError::new(ErrorKind::Timeout, format!("time limit of {timeout:?} exceeded")));
+        }
+        if let Some(max) = limits.max_heap_bytes.filter(|&max| self.heap.live_bytes() > max) {
+            return Err(self.heap_limit_error(max));
+        }
+        let next = if limits.timeout.is_some() || limits.max_heap_bytes.is_some() {
+            self.steps + LIMIT_CHECK_INTERVAL
+        } else {
+            u64::MAX
+        };
+        self.next_limit_check = next.min(limits.max_steps.unwrap_or(u64::MAX));
+        Ok(())
+    }
+
+    /// Calls a function value on behalf of a builtin, like the callback given to `map_err`.
+    pub(crate) fn call(&mut self, function: Object, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+        if self.runs >= MAX_NESTED_RUNS {
+            return Err(RuntimeError::new(ErrorKind::StackOverflow, format!(
+                "maximum depth of {MAX_NESTED_RUNS} builtins calling back exceeded"
+            )));
+        }
+        let call_site = self.builtin_call_site;
+        self.run_call(function, arguments, call_site).map_err(stray_jump)
+    }
+
+    /// Calls a function value from the host, as a new run: the limits are counted anew like for `eval`.
+    pub fn apply(&mut self, function: Object, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+        self.start_run();
+        self.run_call(function, arguments, Span::default()).map_err(stray_jump)
+    }
+
+    /// The top-level variable `name`, or the builtin of that name.
+    pub fn global(&self, name: &str) -> Option<Object> {
+        self.env.borrow().get(name).or_else(|| builtins::lookup(name))
+    }
+
+    /// The names of the globals shared with modules, see `set_global`.
+    pub(crate) fn shared_names(&self) -> Vec<String> {
+        self.shared.borrow().names().cloned().collect()
+    }
+
+    /// Runs a file of the standard library in its own top level, which sees only the builtins, and adds
+    /// its exports to the shared globals. Locations in it are shown as in the file `name`.
+    pub(crate) fn load_prelude(&mut self, name: &str, program: &Program) -> Result<(), RuntimeError> {
+        let env = self.heap.new_environment(None);
+        self.start_run();
+        let caller_file = self.file.replace(name.into());
+        let result = self.run_statements(&program.statements, &env);
+        self.file = caller_file;
+        result.map_err(stray_jump)?;
+        for (name, value) in Evaluator::exports(program, &env) {
+            self.shared.borrow_mut().set(&name, value);
+        }
+        Ok(())
+    }
+
+    /// The values of the variables declared with `export` by the top level of `program`.
+    fn exports(program: &Program, env: &Env) -> Vec<(String, Object)> {
+        let mut exports = vec![];
+        for statement in program.statements.iter() {
+            if let Statement::Export(variable) = statement {
+                if let Statement::Variable { name, .. } = variable.as_ref() {
+                    exports.push((name.clone(), env.borrow().get(name).unwrap_or(Object::Null)));
+                }
+            }
+        }
+        exports
+    }
+
+    /// Declares or replaces the top-level variable `name`, for the main program and for modules. It
+    /// replaces a variable the main program declared with the same name.
+    pub fn set_global(&mut self, name: &str, value: Object) {
+        self.env.borrow_mut().remove(name);
+        self.shared.borrow_mut().set(name, value);
+    }
+
+    /// Runs a program read from the file at `path`, the modules it imports are found relative to it.
+    pub fn eval_file(&mut self, program: &Program, path: &Path) -> Result<Object, RuntimeError> {
+        self.loading.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+        let result = self.eval(program);
+        self.loading.pop();
+        result
+    }
+
+    pub fn eval(&mut self, program: &Program) -> Result<Object, RuntimeError> {
+        let env = self.env.clone();
+        self.start_run();
+        match self.run_statements(&program.statements, &env) {
+            Ok(value) | Err(Interrupt::Return(value)) => Ok(value),
+            Err(interrupt) => Err(stray_jump(interrupt)),
+        }
+    }
+
+    fn run_statements(&mut self, statements: &Rc<[Statement]>, env: &Env) -> EvalResult {
+        let mut machine = Machine::default();
+        machine.push_statements(statements, env.clone());
+        self.run(machine)
+    }
+
+    fn run_call(&mut self, function: Object, arguments: Vec<Object>, call_site: Span) -> EvalResult {
+        let mut machine = Machine::default();
+        self.start_call(function, arguments, call_site, None, &mut machine)?;
+        self.run(machine)
+    }
+
+    /// Performs tasks until none are left, gives back the value they computed or the interrupt none of
+    /// them handled.
+    fn run(&mut self, mut machine: Machine) -> EvalResult {
+        self.runs += 1;
+        let mut result = Ok(());
+        while let Some(task) = machine.tasks.pop() {
+            if let Err(interrupt) = self.perform(task, &mut machine) {
+                result = self.unwind(interrupt, &mut machine);
+            }
+        }
+        self.runs -= 1;
+        result.map(|_| machine.pop())
+    }
+
+    /// Drops tasks until one of them handles the interrupt, usually by pushing the tasks to go on with.
+    fn unwind(&mut self, mut interrupt: Interrupt, machine: &mut Machine) -> Result<(), Interrupt> {
+        while let Some(task) = machine.tasks.pop() {
+            interrupt = match self.handle(task, interrupt, machine) {
+                Ok(()) => return Ok(()),
+                Err(interrupt) => interrupt,
+            };
+        }
+        Err(interrupt)
+    }
+
+    fn perform(&mut self, task: Task, machine: &mut Machine) -> Result<(), Interrupt> {
+        match task {
+            Task::Eval(expression, env) => self.eval_expression(expression, env, machine),
+            Task::Statements { statements, next, env } => {
+                // the value of the statement before is replaced by the value of this one
+                machine.pop();
+                if next + 1 < statements.len() {
+                    let rest = Task::Statements { statements: statements.clone(), next: next + 1, env: env.clone() };
+                    machine.tasks.push(rest);
+                }
+                self.eval_statement(&statements[next], &env, machine)
+            }
+            Task::Apply(expression, env) => {
+                self.apply_expression(&expression, &env, machine).map_err(|interrupt| blame(interrupt, expression.span()))
+            }
+            Task::Blame(_) => Ok(()),
+            Task::Declare(name, env) => {
+                let value = machine.pop();
+                env.borrow_mut().set(&name, value);
+                machine.values.push(Object::Null);
+                Ok(())
+            }
+            Task::Return => Err(Interrupt::Return(machine.pop())),
+            Task::TailCall { arguments, call_site } => {
+                let arguments = machine.pop_many(arguments);
+                let function = machine.pop();
+                Err(Interrupt::TailCall(Box::new(TailCall { function, arguments, call_site })))
+            }
+            Task::Throw(span) => Err(Evaluator::thrown_error(machine.pop(), span).into()),
+            Task::Init(state) | Task::Step(state) => {
+                machine.pop();
+                self.test_loop(state, machine)
+            }
+            Task::Iterate(mut state, iterable) => {
+                let value = machine.pop();
+                let items = Evaluator::iterate(&value).map_err(|mut e| {
+                    e.span = iterable.span();
+                    e
+                })?;
+                if let LoopKind::ForIn { items: pending, .. } = &mut state.kind {
+                    *pending = items.into_iter();
+                }
+                self.next_iteration(state, machine)
+            }
+            Task::Test(state) => {
+                let value = machine.pop();
+                let condition = state.condition().expect("only loops with a condition are tested");
+                if self.test(&value, condition)? {
+                    let env = state.env.clone();
+                    self.run_body(state, &env, machine)
+                } else {
+                    machine.values.push(Object::Null);
+                    Ok(())
+                }
+            }
+            Task::Loop(state) => {
+                machine.pop();
+                self.next_iteration(state, machine)
+            }
+            Task::Try(attempt) => self.finish_try(attempt, machine),
+            Task::Call(_) => {
+                self.call_depth -= 1;
+                Ok(())
+            }
+        }
+    }
+
+    /// Lets the task handle the interrupt, gives the interrupt back for the tasks below when it does not.
+    fn handle(&mut self, task: Task, interrupt: Interrupt, machine: &mut Machine) -> Result<(), Interrupt> {
+        match (task, interrupt) {
+            (Task::Apply(expression, _), interrupt) => Err(blame(interrupt, expression.span())),
+            (Task::Blame(span), interrupt) => Err(blame(interrupt, Some(span))),
+            (Task::Loop(state), Interrupt::Break(label)) if state.is_target(&label) => {
+                machine.values.truncate(state.height);
+                machine.values.push(Object::Null);
+                Ok(())
+            }
+            (Task::Loop(state), Interrupt::Continue(label)) if state.is_target(&label) => {
+                machine.values.truncate(state.height);
+                self.next_iteration(state, machine)
+            }
+            (Task::Try(attempt), interrupt) => self.handle_try(attempt, interrupt, machine),
+            (Task::Call(frame), interrupt) => self.leave_call(*frame, interrupt, machine),
+            (_, interrupt) => Err(interrupt),
+        }
+    }
+
+    fn eval_statement(&mut self, statement: &Statement, env: &Env, machine: &mut Machine) -> Result<(), Interrupt> {
+        match statement {
+            Statement::Variable { name, value, .. } => {
+                machine.tasks.push(Task::Declare(name.clone(), env.clone()));
+                machine.tasks.push(Task::Eval(value.clone(), env.clone()));
+            }
+            Statement::Return { value, .. } => match value.as_ref() {
+                // outside of a function there is no call to leave, so the call is made right away
+                Expression::Call { function, arguments, span } if self.call_depth > 0 => {
+                    machine.tasks.push(Task::TailCall { arguments: arguments.len(), call_site: *span });
+                    machine.push_evals(arguments.iter().rev().chain([function]), env);
+                }
+                _ => {
+                    machine.tasks.push(Task::Return);
+                    machine.tasks.push(Task::Eval(value.clone(), env.clone()));
+                }
+            },
+            Statement::Expression(expression) => machine.tasks.push(Task::Eval(expression.clone(), env.clone())),
+            Statement::While { label, condition, body } => {
+                let kind = LoopKind::While { condition: condition.clone() };
+                return self.test_loop(Loop::new(label, kind, body, env.clone()), machine);
+            }
+            Statement::ForIn { label, variable, iterable, body } => {
+                let kind = LoopKind::ForIn { variable: variable.clone(), items: vec![].into_iter() };
+                machine.tasks.push(Task::Iterate(Loop::new(label, kind, body, env.clone()), iterable.clone()));
+                machine.tasks.push(Task::Eval(iterable.clone(), env.clone()));
+            }
+            Statement::For { label, init, condition, step, body } => {
+                let kind = LoopKind::For { condition: condition.clone(), step: step.clone() };
+                let state = Loop::new(label, kind, body, self.heap.new_environment(Some(env.clone())));
+                match init {
+                    Some(init) => {
+                        let env = state.env.clone();
+                        machine.tasks.push(Task::Init(state));
+                        return self.eval_statement(init, &env, machine);
+                    }
+                    None => return self.test_loop(state, machine),
+                }
+            }
+            Statement::Break(label) => return Err(Interrupt::Break(label.clone())),
+            Statement::Continue(label) => return Err(Interrupt::Continue(label.clone())),
+            Statement::Try { body, catch, finally } => {
+                machine.tasks.push(Task::Try(Box::new(Try {
+                    catch: catch.clone(),
+                    finally: finally.clone(),
+                    env: env.clone(),
+                    stage: TryStage::Body,
+                    height: machine.values.len(),
+                })));
+                self.run_block(body, env, machine);
+            }
+            Statement::Throw { value, span } => {
+                machine.tasks.push(Task::Throw(*span));
+                machine.tasks.push(Task::Eval(value.clone(), env.clone()));
+            }
+            Statement::Import { path, name, span } => {
+                let exports = self.import(path, *span).map_err(|interrupt| with_span(interrupt, *span))?;
+                env.borrow_mut().set(name, exports);
+                machine.values.push(Object::Null);
+            }
+            Statement::Export(statement) => return self.eval_statement(statement, env, machine),
+        }
+        Ok(())
+    }
+
+    /// Runs the statements of a block in a scope of their own.
+    fn run_block(&mut self, block: &BlockStatement, env: &Env, machine: &mut Machine) {
+        let block_env = self.heap.new_environment(Some(env.clone()));
+        machine.push_statements(&block.statements, block_env);
+    }
+
+    /// Runs the module at `path` the first time it is imported, gives back the hash of its exports.
+    fn import(&mut self, path: &str, span: Span) -> EvalResult {
+        self.check_io("import")?;
+        let file = module::locate(path, self.loading.last())?;
+        if let Some(exports) = self.modules.get(&file) {
+            return Ok(exports.clone());
+        }
+        if self.loading.contains(&file) {
+            return Err(module::cycle_error(&self.loading, &file).into());
+        }
+        let name: Rc<str> = module::display(&file, self.loading.first()).into();
+        let frame = Frame { function: format!("<module {name}>"), call_site: span, file: Some(name.clone()) };
+        let program = module::load(&file, &self.shared_names()).map_err(|mut e| {
+            e.frames.push(frame.clone());
+            e
+        })?;
+
+        // modules have their own top level, they see the shared globals but not the variables of the importer
+        let env = self.heap.new_environment(Some(self.shared.clone()));
+        self.loading.push(file);
+        let caller_file = self.file.replace(name);
+        let result = self.run_statements(&program.statements, &env);
+        self.file = caller_file;
+        let file = self.loading.pop().expect("the module was pushed above");
+        if let Err(interrupt) = result {
+            let mut e = stray_jump(interrupt);
+            e.frames.push(frame);
+            return Err(e.into());
+        }
+
+        let mut exports = Hash::new();
+        for (name, value) in Evaluator::exports(&program, &env) {
+            exports.insert(Object::String(name), value)?;
+        }
+        let exports = self.heap.new_hash(exports);
+        self.modules.insert(file, exports.clone());
+        Ok(exports)
+    }
+
+    /// Starts an iteration of a while or for loop, testing its condition first if it has one.
+    fn test_loop(&mut self, state: Box<Loop>, machine: &mut Machine) -> Result<(), Interrupt> {
+        match state.condition() {
+            Some(condition) => {
+                let eval = Task::Eval(condition.clone(), state.env.clone());
+                machine.tasks.push(Task::Test(state));
+                machine.tasks.push(eval);
+                Ok(())
+            }
+            None => {
+                let env = state.env.clone();
+                self.run_body(state, &env, machine)
+            }
+        }
+    }
+
+    /// Goes on with a loop whose body completed or continued.
+    fn next_iteration(&mut self, mut state: Box<Loop>, machine: &mut Machine) -> Result<(), Interrupt> {
+        match &mut state.kind {
+            LoopKind::While { .. } => self.test_loop(state, machine),
+            LoopKind::ForIn { variable, items } => match items.next() {
+                Some(item) => {
+                    // every iteration gets its own binding, closures created in the body keep their item
+                    let iteration_env = self.heap.new_environment(Some(state.env.clone()));
+                    iteration_env.borrow_mut().set(variable, item);
+                    self.run_body(state, &iteration_env, machine)
+                }
+                None => {
+                    machine.values.push(Object::Null);
+                    Ok(())
+                }
+            },
+            LoopKind::For { step, .. } => {
+                let step = step.clone();
+                // the step runs on a fresh copy of the loop variables, so closures created
+                // during this iteration do not observe the increment
+                state.env = self.heap.copy_environment(&state.env);
+                match step {
+                    Some(step) => {
+                        let eval = Task::Eval(step, state.env.clone());
+                        machine.tasks.push(Task::Step(state));
+                        machine.tasks.push(eval);
+                        Ok(())
+                    }
+                    None => self.test_loop(state, machine),
+                }
+            }
+        }
+    }
+
+    /// Runs one iteration of a loop body, under the task that waits for it.
+    fn run_body(&mut self, mut state: Box<Loop>, env: &Env, machine: &mut Machine) -> Result<(), Interrupt> {
+        // loops without a condition evaluate no expression when their body is empty
+        self.step()?;
+        state.height = machine.values.len();
+        let body = state.body.clone();
+        machine.tasks.push(Task::Loop(state));
+        self.run_block(&body, env, machine);
+        Ok(())
+    }
+
+    /// Goes on with a try statement whose body, catch or finally block completed.
+    fn finish_try(&mut self, mut attempt: Box<Try>, machine: &mut Machine) -> Result<(), Interrupt> {
+        match std::mem::replace(&mut attempt.stage, TryStage::Body) {
+            TryStage::Finally(pending) => {
+                machine.pop();
+                machine.values.push(pending?);
+                Ok(())
+            }
+            _ if attempt.finally.is_some() => {
+                let value = machine.pop();
+                self.run_finally(attempt, Ok(value), machine);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn handle_try(&mut self, mut attempt: Box<Try>, interrupt: Interrupt, machine: &mut Machine) -> Result<(), Interrupt> {
+        machine.values.truncate(attempt.height);
+        match (&attempt.stage, interrupt) {
+            // a finally block that completes normally lets the pending result or interrupt through,
+            // leaving it with return, break, continue or an error replaces them
+            (TryStage::Finally(_), interrupt) => Err(interrupt),
+            // tail calls are made inside the try, so catch sees their errors and finally runs after them
+            (_, Interrupt::TailCall(call)) => {
+                machine.tasks.push(Task::Try(attempt));
+                machine.tasks.push(Task::Return);
+                self.start_call(call.function, call.arguments, call.call_site, None, machine)
+                    .map_err(|interrupt| with_span(interrupt, call.call_site))
+            }
+            // the script must not get to run any further
+            (_, Interrupt::Error(e)) if e.kind.is_fatal() => Err(Interrupt::Error(e)),
+            (TryStage::Body, Interrupt::Error(e)) if attempt.catch.is_some() => {
+                let (name, handler) = attempt.catch.clone().expect("checked above");
+                let catch_env = self.heap.new_environment(Some(attempt.env.clone()));
+                catch_env.borrow_mut().set(&name, Object::Error(Rc::new(*e)));
+                attempt.stage = TryStage::Catch;
+                machine.tasks.push(Task::Try(attempt));
+                self.run_block(&handler, &catch_env, machine);
+                Ok(())
+            }
+            (_, interrupt) if attempt.finally.is_some() => {
+                self.run_finally(attempt, Err(interrupt), machine);
+                Ok(())
+            }
+            (_, interrupt) => Err(interrupt),
+        }
+    }
+
+    fn run_finally(&mut self, mut attempt: Box<Try>, pending: EvalResult, machine: &mut Machine) {
+        let finally = attempt.finally.clone().expect("only called for a try with finally");
+        let env = attempt.env.clone();
+        attempt.stage = TryStage::Finally(pending);
+        machine.tasks.push(Task::Try(attempt));
+        self.run_block(&finally, &env, machine);
+    }
+
+    fn thrown_error(value: Object, span: Span) -> RuntimeError {
+        match value {
+            // rethrowing a caught error keeps its kind, location and trace
+            Object::Error(error) => (*error).clone(),
+            value => {
+                let mut error = RuntimeError::new(ErrorKind::Error, value.to_string());
+                error.span = Some(span);
+                error.value = Some(value);
+                error
+            }
+        }
+    }
+
+    /// Evaluates a condition of an if or a loop, a value unusable as a condition is blamed on the condition.
+    fn test(&self, value: &Object, condition: &Expression) -> Result<bool, Interrupt> {
+        self.is_truthy(value).map_err(|mut e| {
+            e.span = condition.span();
+            e.into()
+        })
+    }
+
+    fn iterate(iterable: &Object) -> Result<Vec<Object>, RuntimeError> {
+        match iterable {
+            // iterate over a snapshot, so the body may modify the array
+            Object::Array(elements) => Ok(elements.borrow().clone()),
+            Object::Hash(hash) => Ok(hash.borrow().keys()),
+            other => Err(RuntimeError::new(ErrorKind::TypeError, format!("not iterable: {}", other.type_name()))),
+        }
+    }
+
+    /// Pushes the value of an expression without operands, or the tasks evaluating its operands, left to
+    /// right, and then applying it. Errors without a location yet are blamed on the expression.
+    fn eval_expression(&mut self, expression: Rc<Expression>, env: Env, machine: &mut Machine) -> Result<(), Interrupt> {
+        self.step().map_err(|interrupt| blame(interrupt, expression.span()))?;
+        let value = match expression.as_ref() {
+            Expression::Identifier { name, span } => {
+                Evaluator::eval_identifier(name, &env).map_err(|interrupt| blame(interrupt, Some(*span)))?
+            }
+            Expression::Integer(value) => Object::Integer(*value),
+            Expression::BigInteger(value) => Object::from_big_integer(value.clone()),
+            Expression::Double(value) => Object::Double(*value),
+            Expression::Boolean(value) => Object::Boolean(*value),
+            Expression::String(value) => Object::String(value.clone()),
+            Expression::Null => Object::Null,
+            Expression::Function { name, parameters, body, .. } => self.new_function(name, parameters, body, &env),
+            _ => {
+                machine.tasks.push(Task::Apply(expression.clone(), env.clone()));
+                Evaluator::push_operands(&expression, &env, machine);
+                return Ok(());
+            }
+        };
+        machine.values.push(value);
+        Ok(())
+    }
+
+    fn push_operands(expression: &Expression, env: &Env, machine: &mut Machine) {
+        match expression {
+            Expression::Interpolated(parts) => {
+                let expressions = parts.iter().filter_map(|part| match part {
+                    StringPart::Expression { expression, .. } => Some(expression),
+                    StringPart::Literal(_) => None,
+                });
+                machine.push_evals(expressions.rev(), env);
+            }
+            // the right operand is only evaluated when it is needed
+            Expression::Infix { operator: TokenType::NullCoalescing, left, .. } => machine.push_evals([left], env),
+            Expression::Infix { left, right, .. } => machine.push_evals([right, left], env),
+            Expression::Prefix { right: operand, .. }
+            | Expression::Assign { value: operand, .. }
+            | Expression::If { condition: operand, .. }
+            | Expression::Member { object: operand, .. }
+            | Expression::Propagate { value: operand, .. } => machine.push_evals([operand], env),
+            Expression::Call { function, arguments, .. } => {
+                machine.push_evals(arguments.iter().rev().chain([function]), env);
+            }
+            Expression::Array(elements) => machine.push_evals(elements.iter().rev(), env),
+            Expression::Hash(pairs) => {
+                machine.push_evals(pairs.iter().rev().flat_map(|(key, value)| [value, key]), env);
+            }
+            Expression::Index { left, index, .. } => machine.push_evals([index, left], env),
+            Expression::Slice { left, start, end, .. } => {
+                machine.push_evals(end.iter().chain(start).chain([left]), env);
+            }
+            _ => unreachable!("{expression} has no operands"),
+        }
+    }
+
+    /// Pops the values of the operands of the expression and pushes its value, or the tasks computing it.
+    fn apply_expression(&mut self, expression: &Expression, env: &Env, machine: &mut Machine) -> Result<(), Interrupt> {
+        let value = match expression {
+            Expression::Interpolated(parts) => self.eval_interpolated(parts, machine)?,
+            Expression::Prefix { operator, .. } => {
+                let right = machine.pop();
+                self.eval_prefix_expression(*operator, right)?
+            }
+            Expression::Infix { operator: TokenType::NullCoalescing, right, span, .. } => match machine.pop() {
+                Object::Null => {
+                    machine.tasks.push(Task::Blame(*span));
+                    machine.tasks.push(Task::Eval(right.clone(), env.clone()));
+                    return Ok(());
+                }
+                value => value,
+            },
+            Expression::Infix { operator, .. } => {
+                let right = machine.pop();
+                let left = machine.pop();
+                self.eval_infix_expression(*operator, left, right)?
+            }
+            Expression::Assign { name, .. } => Evaluator::assign(name, machine.pop(), env)?,
+            // an if without else whose condition does not hold evaluates to null
+            Expression::If { condition, consequence, alternative } => {
+                let block = if self.test(&machine.pop(), condition)? { Some(consequence) } else { alternative.as_ref() };
+                match block {
+                    Some(block) => {
+                        self.run_block(block, env, machine);
+                        return Ok(());
+                    }
+                    None => Object::Null,
+                }
+            }
+            Expression::Call { arguments, span, .. } => {
+                let arguments = machine.pop_many(arguments.len());
+                let function = machine.pop();
+                return self.start_call(function, arguments, *span, Some(*span), machine);
+            }
+            Expression::Array(elements) => {
+                let elements = machine.pop_many(elements.len());
+                self.check_size(elements.len() * size_of::<Object>())?;
+                self.heap.new_array(elements)
+            }
+            Expression::Hash(pairs) => {
+                let mut hash = Hash::new();
+                let mut values = machine.pop_many(pairs.len() * 2).into_iter();
+                while let (Some(key), Some(value)) = (values.next(), values.next()) {
+                    hash.insert(key, value)?;
+                }
+                self.check_size(pairs.len() * 2 * size_of::<Object>())?;
+                self.heap.new_hash(hash)
+            }
+            Expression::Index { .. } => {
+                let index = machine.pop();
+                let left = machine.pop();
+                Evaluator::eval_index_expression(left, index)?
+            }
+            Expression::Slice { start, end, .. } => {
+                let end = end.as_ref().map(|_| machine.pop());
+                let start = start.as_ref().map(|_| machine.pop());
+                let left = machine.pop();
+                self.eval_slice_expression(left, start, end)?
+            }
+            Expression::Member { property, optional, .. } => {
+                Evaluator::eval_member_expression(machine.pop(), property, *optional)?
+            }
+            Expression::Propagate { .. } => Evaluator::propagate(machine.pop())?,
+            _ => unreachable!("{expression} has no operands"),
+        };
+        machine.values.push(value);
+        Ok(())
+    }
+
+    fn eval_identifier(name: &str, env: &Env) -> EvalResult {
+        match env.borrow().get(name).or_else(|| builtins::lookup(name)) {
+            Some(value) => Ok(value),
+            None => Err(error(ErrorKind::NameError, format!("identifier not found: {name}"))),
+        }
+    }
+
+    fn assign(name: &str, value: Object, env: &Env) -> EvalResult {
+        if env.borrow_mut().assign(name, value.clone()) {
+            Ok(value)
+        } else {
+            Err(error(ErrorKind::NameError, format!("identifier not found: {name}")))
+        }
+    }
+
+    fn new_function(&mut self, name: &Option<String>, parameters: &[String], body: &BlockStatement, env: &Env) -> Object {
+        self.heap.new_function(Function {
+            name: name.clone(),
+            parameters: parameters.to_vec(),
+            body: body.clone(),
+            env: env.clone(),
+            file: self.file.clone(),
+        })
+    }
+
+    // an Err is returned as it is, at the top level it ends the program
+    fn propagate(value: Object) -> EvalResult {
+        match value {
+            Object::Ok(value) => Ok(*value),
+            error @ Object::Err(_) => Err(Interrupt::Return(error)),
+            other => Err(error(ErrorKind::TypeError, format!("? operator requires RESULT, got {}", other.type_name()))),
+        }
+    }
+
+    fn eval_interpolated(&mut self, parts: &[StringPart], machine: &mut Machine) -> EvalResult {
+        let count = parts.iter().filter(|part| matches!(part, StringPart::Expression { .. })).count();
+        let mut values = machine.pop_many(count).into_iter();
+        let mut string = String::new();
+        for part in parts {
+            match part {
+                StringPart::Literal(value) => string.push_str(value),
+                StringPart::Expression { format, .. } => {
+                    let value = values.next().expect("one value per expression");
+                    let text = match format {
+                        Some(format) => value.format(format),
+                        None => value.to_string(),
+                    };
+                    self.check_size(string.len().saturating_add(text.len()))?;
+                    string.push_str(&text);
+                }
+            }
+        }
+        Ok(Object::String(string))
+    }
+
+    /// A call evaluates to the value given to the `return` that ends it. A function body left without
+    /// `return` evaluates to its last statement: the value of a trailing expression, or null after a
+    /// declaration, a loop or an empty body.
+    /// Builtins and natives are called right away, for functions the call pushes the tasks running the
+    /// body, under a frame handling what leaves it. Errors raised in the body record the call in their
+    /// frames and are blamed on `blame`, the call expression, if nothing in the body had a location for
+    /// them. Errors of the call itself, like a wrong number of arguments, are returned as they are.
+    fn start_call(
+        &mut self, function: Object, arguments: Vec<Object>, call_site: Span, blame: Option<Span>, machine: &mut Machine,
+    ) -> Result<(), Interrupt> {
+        let function = match function {
+            Object::Function(function) => function,
+            Object::Builtin(builtin) => {
+                let caller_site = std::mem::replace(&mut self.builtin_call_site, call_site);
+                let result = (builtin.function)(self, arguments);
+                self.builtin_call_site = caller_site;
+                machine.values.push(result?);
+                return Ok(());
+            }
+            Object::Native(native) => {
+                machine.values.push(native.call(arguments)?);
+                return Ok(());
+            }
+            other => return Err(error(ErrorKind::TypeError, format!("not a function: {}", other.type_name()))),
+        };
+        if function.parameters.len() != arguments.len() {
+            return Err(error(ErrorKind::ArgumentError, format!(
+                "wrong number of arguments: expected {}, got {}", function.parameters.len(), arguments.len()
+            )));
+        }
+
+        if self.call_depth >= self.config.max_call_depth {
+            return Err(error(ErrorKind::StackOverflow, format!(
+                "maximum call depth of {} exceeded", self.config.max_call_depth
+            )));
+        }
+
+        let env = self.heap.new_environment(Some(function.env.clone()));
+        for (parameter, argument) in function.parameters.iter().zip(arguments) {
+            env.borrow_mut().set(parameter, argument);
+        }
+        self.call_depth += 1;
+        let statements = function.body.statements.clone();
+        machine.tasks.push(Task::Call(Box::new(CallFrame { function, call_site, blame, height: machine.values.len() })));
+        machine.push_statements(&statements, env);
+        Ok(())
+    }
+
+    /// Ends a call left by an interrupt. A `return f(x)` is made here, replacing the call left, so tail
+    /// calls do not nest: traces show only the last of them.
+    fn leave_call(&mut self, frame: CallFrame, interrupt: Interrupt, machine: &mut Machine) -> Result<(), Interrupt> {
+        self.call_depth -= 1;
+        machine.values.truncate(frame.height);
+        match interrupt {
+            Interrupt::Return(value) => {
+                machine.values.push(value);
+                Ok(())
+            }
+            Interrupt::TailCall(call) => self.start_call(call.function, call.arguments, call.call_site, frame.blame, machine)
+                .map_err(|interrupt| blame(with_span(interrupt, call.call_site), frame.blame)),
+            interrupt => {
+                let mut e = stray_jump(interrupt);
+                let name = frame.function.name.clone().unwrap_or_else(|| "<anonymous>".to_string());
+                e.frames.push(Frame { function: name, call_site: frame.call_site, file: frame.function.file.clone() });
+                Err(blame(e.into(), frame.blame))
+            }
+        }
+    }
+
+    fn eval_index_expression(left: Object, index: Object) -> EvalResult {
+        match (left, index) {
+            (Object::Array(elements), Object::Integer(index)) => {
+                let elements = elements.borrow();
+                match Evaluator::normalize_index(index, elements.len()) {
+                    Some(position) if position < elements.len() => Ok(elements[position].clone()),
+                    _ => Err(out_of_bounds(index, elements.len())),
+                }
+            }
+            (Object::Array(elements), Object::BigInteger(index)) => {
+                Err(out_of_bounds(index, elements.borrow().len()))
+            }
+            (Object::Array(_), index) => {
+                Err(error(ErrorKind::TypeError, format!("array index must be INTEGER, got {}", index.type_name())))
+            }
+            // strings are indexed by character, not by byte
+            (Object::String(value), Object::Integer(index)) => {
+                let length = value.chars().count();
+                match Evaluator::normalize_index(index, length) {
+                    Some(position) if position < length => Ok(Object::String(value.chars().nth(position).unwrap().to_string())),
+                    _ => Err(out_of_bounds(index, length)),
+                }
+            }
+            (Object::String(value), Object::BigInteger(index)) => {
+                Err(out_of_bounds(index, value.chars().count()))
+            }
+            (Object::String(_), index) => {
+                Err(error(ErrorKind::TypeError, format!("string index must be INTEGER, got {}", index.type_name())))
+            }
+            (Object::Hash(hash), key) => match hash.borrow().get(&key)? {
+                Some(value) => Ok(value),
+                None => Err(error(ErrorKind::KeyError, format!("key not found: {}", key.inspect()))),
+            },
+            (left, _) => Err(error(ErrorKind::TypeError, format!("index operator not supported: {}", left.type_name()))),
+        }
+    }
+
+    /// `object.property` reads the string key `property` of a hash, or a field of a caught error. The optional
+    /// form `object?.property` evaluates to null instead of failing when the object is null or the key is missing.
+    fn eval_member_expression(object: Object, property: &str, optional: bool) -> EvalResult {
+        match object {
+            Object::Null if optional => Ok(Object::Null),
+            Object::Null => Err(error(ErrorKind::TypeError, format!("cannot access property '{property}' of null"))),
+            Object::Hash(hash) => match hash.borrow().get(&Object::String(property.to_string()))? {
+                Some(value) => Ok(value),
+                None if optional => Ok(Object::Null),
+                None => Err(error(ErrorKind::KeyError, format!("key not found: {property:?}"))),
+            },
+            Object::Error(caught) => match property {
+                "message" => Ok(Object::String(caught.message.clone())),
+                "kind" => Ok(Object::String(caught.kind.to_string())),
+                "trace" => Ok(Object::String(caught.traceback())),
+                // the value given to `throw`, null for errors raised by the interpreter
+                "value" => Ok(caught.value.clone().unwrap_or(Object::Null)),
+                _ if optional => Ok(Object::Null),
+                _ => Err(error(ErrorKind::TypeError, format!("unknown property '{property}' of ERROR"))),
+            },
+            other => Err(error(ErrorKind::TypeError, format!("property access not supported: {}", other.type_name()))),
+        }
+    }
+
+    fn eval_slice_expression(&mut self, left: Object, start: Option<Object>, end: Option<Object>) -> EvalResult {
+        let length = match &left {
+            Object::Array(elements) => elements.borrow().len(),
+            Object::String(value) => value.chars().count(),
+            other => {
+                return Err(error(ErrorKind::TypeError, format!("slice operator not supported: {}", other.type_name())))
+            }
+        };
+        let start = start.unwrap_or(Object::Integer(0));
+        let end = end.unwrap_or(Object::Integer(length as i64));
+        let position = |bound: &Object| match bound {
+            Object::Integer(bound) => Ok(Evaluator::normalize_index(*bound, length)),
+            Object::BigInteger(_) => Ok(None),
+            other => Err(error(ErrorKind::TypeError, format!("slice bounds must be INTEGER, got {}", other.type_name()))),
+        };
+        match (position(&start)?, position(&end)?) {
+            (Some(from), Some(to)) if from <= to => match left {
+                Object::String(value) => Ok(Object::String(value.chars().skip(from).take(to - from).collect())),
+                Object::Array(elements) => {
+                    let elements = elements.borrow()[from..to].to_vec();
+                    Ok(self.heap.new_array(elements))
+                }
+                _ => unreachable!("checked above"),
+            },
+            _ => Err(error(ErrorKind::IndexError, format!("slice out of bounds: {start}:{end}, length {length}"))),
+        }
+    }
+
+    /// Turns a possibly negative index into a position in 0..=length, counting negative
+    /// indexes from the end. Callers accessing an element still have to exclude `length`.
+    fn normalize_index(index: i64, length: usize) -> Option<usize> {
+        let position = if index < 0 { index.checked_add(length as i64)? } else { index };
+        if position >= 0 && position as u64 <= length as u64 {
+            Some(position as usize)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn is_truthy(&self, value: &Object) -> Result<bool, RuntimeError> {
+        match (self.config.truthiness, value) {
+            (_, Object::Boolean(value)) => Ok(*value),
+            (Truthiness::Strict, other) => Err(RuntimeError::new(ErrorKind::TypeError, format!(
+                "strict truthiness: expected BOOLEAN, got {}", other.type_name()
+            ))),
+            (Truthiness::Loose, Object::Null) => Ok(false),
+            (Truthiness::Loose, Object::Integer(value)) => Ok(*value != 0),
+            (Truthiness::Loose, Object::BigInteger(_)) => Ok(true),
+            (Truthiness::Loose, Object::Double(value)) => Ok(*value != 0.0),
+            (Truthiness::Loose, Object::String(value)) => Ok(!value.is_empty()),
+            (Truthiness::Loose, Object::Array(elements)) => Ok(!elements.borrow().is_empty()),
+            (Truthiness::Loose, Object::Hash(hash)) => Ok(!hash.borrow().is_empty()),
+            (Truthiness::Loose, Object::Function(_) | Object::Builtin(_) | Object::Native(_) | Object::Error(_)) => Ok(true),
+            (Truthiness::Loose, Object::Ok(_) | Object::Err(_)) => Ok(true),
+        }
+    }
+
+    /// Whether `left < right` holds, with the same rules as the operator.
+    pub(crate) fn less_than(&self, left: &Object, right: &Object) -> Result<bool, RuntimeError> {
+        match self.eval_infix_expression(TokenType::LessThan, left.clone(), right.clone()) {
+            Ok(value) => Ok(matches!(value, Object::Boolean(true))),
+            Err(interrupt) => Err(stray_jump(interrupt)),
+        }
+    }
+
+    fn eval_prefix_expression(&self, operator: TokenType, right: Object) -> EvalResult {
+        match (operator, right) {
+            (TokenType::Bang, right) => Ok(Object::Boolean(!self.is_truthy(&right)?)),
+            (TokenType::Minus, Object::Integer(value)) => match value.overflowing_neg() {
+                (_, true) if self.config.overflow == IntegerOverflow::Promote => {
+                    Ok(Object::from_big_integer(-&BigInt::from(value)))
+                }
+                (negated, _) => Ok(Object::Integer(negated)),
+            },
+            (TokenType::Minus, Object::BigInteger(value)) => Ok(Object::from_big_integer(-&value)),
+            (TokenType::Minus, Object::Double(value)) => Ok(Object::Double(-value)),
+            (operator, right) => {
+                Err(error(ErrorKind::TypeError, format!("unknown operator: {}{}", operator, right.type_name())))
+            }
+        }
+    }
+
+    /// Arithmetic between two integers stays integral, `/` truncates toward zero. Integers are 64 bit
+    /// and promoted to big integers when a result does not fit (or wrap, if so configured). As soon
+    /// as one operand is a double, the other one is promoted and the result follows IEEE 754, so
+    /// double division by zero gives an infinity or NaN, and NaN is unordered and unequal to
+    /// everything. Integers and doubles are compared by their exact values, without promotion.
+    fn eval_infix_expression(&self, operator: TokenType, left: Object, right: Object) -> EvalResult {
+        match (left, right) {
+            (Object::Integer(left), Object::Integer(right)) => self.eval_integer_infix_expression(operator, left, right),
+            (Object::Double(left), Object::Double(right)) => Evaluator::eval_double_infix_expression(operator, left, right),
+            (Object::Integer(left), Object::Double(right)) => match Evaluator::compare(operator, compare_integer_double(left, right)) {
+                Some(value) => Ok(value),
+                None => Evaluator::eval_double_infix_expression(operator, left as f64, right),
+            },
+            (Object::Double(left), Object::Integer(right)) => {
+                let ordering = compare_integer_double(right, left).map(Ordering::reverse);
+                match Evaluator::compare(operator, ordering) {
+                    Some(value) => Ok(value),
+                    None => Evaluator::eval_double_infix_expression(operator, left, right as f64),
+                }
+            }
+            (Object::BigInteger(left), Object::Double(right)) => match Evaluator::compare(operator, compare_big_integer_double(&left, right)) {
+                Some(value) => Ok(value),
+                None => Evaluator::eval_double_infix_expression(operator, left.to_f64(), right),
+            },
+            (Object::Double(left), Object::BigInteger(right)) => {
+                let ordering = compare_big_integer_double(&right, left).map(Ordering::reverse);
+                match Evaluator::compare(operator, ordering) {
+                    Some(value) => Ok(value),
+                    None => Evaluator::eval_double_infix_expression(operator, left, right.to_f64()),
+                }
+            }
+            (left @ (Object::Integer(_) | Object::BigInteger(_)), right @ (Object::Integer(_) | Object::BigInteger(_))) => {
+                Evaluator::eval_big_integer_infix_expression(operator, &to_big_integer(left), &to_big_integer(right))
+            }
+            (Object::String(left), Object::String(right)) => self.eval_string_infix_expression(operator, left, right),
+            (Object::Boolean(left), Object::Boolean(right)) if operator == TokenType::Equal => Ok(Object::Boolean(left == right)),
+            (Object::Boolean(left), Object::Boolean(right)) if operator == TokenType::NotEqual => Ok(Object::Boolean(left != right)),
+            // anything can be compared with null
+            (Object::Null, other) | (other, Object::Null) if matches!(operator, TokenType::Equal | TokenType::NotEqual) => {
+                let equal = matches!(other, Object::Null);
+                Ok(Object::Boolean(equal == (operator == TokenType::Equal)))
+            }
+            (left, right) if left.type_name() != right.type_name() => Err(error(ErrorKind::TypeError, format!(
+                "type mismatch: {} {} {}", left.type_name(), operator, right.type_name()
+            ))),
+            (left, right) => Err(error(ErrorKind::TypeError, format!(
+                "unknown operator: {} {} {}", left.type_name(), operator, right.type_name()
+            ))),
+        }
+    }
+
+    /// Evaluates a comparison operator given the ordering of its operands, `None` standing for
+    /// unordered operands. Returns None if the operator is not a comparison.
+    fn compare(operator: TokenType, ordering: Option<Ordering>) -> Option<Object> {
+        let value = match operator {
+            TokenType::Equal => ordering == Some(Ordering::Equal),
+            TokenType::NotEqual => ordering != Some(Ordering::Equal),
+            TokenType::LessThan => ordering == Some(Ordering::Less),
+            TokenType::GreaterThan => ordering == Some(Ordering::Greater),
+            TokenType::LessOrEqual => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+            TokenType::GreaterOrEqual => matches!(ordering, Some(Ordering::Greater | Ordering::Equal)),
+            _ => return None,
+        };
+        Some(Object::Boolean(value))
+    }
+
+    /// Strings are concatenated with `+` and ordered by their characters' code points.
+    fn eval_string_infix_expression(&self, operator: TokenType, left: String, right: String) -> EvalResult {
+        if let Some(value) = Evaluator::compare(operator, Some(left.cmp(&right))) {
+            return Ok(value);
+        }
+        match operator {
+            TokenType::Plus => {
+                self.check_size(left.len() + right.len())?;
+                Ok(Object::String(left + &right))
+            }
+            _ => Err(error(ErrorKind::TypeError, format!("unknown operator: STRING {operator} STRING"))),
+        }
+    }
+
+    fn eval_integer_infix_expression(&self, operator: TokenType, left: i64, right: i64) -> EvalResult {
+        if let Some(value) = Evaluator::compare(operator, Some(left.cmp(&right))) {
+            return Ok(value);
+        }
+        let (value, overflowed) = match operator {
+            TokenType::Plus => left.overflowing_add(right),
+            TokenType::Minus => left.overflowing_sub(right),
+            TokenType::Asterisk => left.overflowing_mul(right),
+            TokenType::Slash if right == 0 => {
+                return Err(error(ErrorKind::ArithmeticError, "division by zero".to_string()))
+            }
+            TokenType::Slash => left.overflowing_div(right),
+            _ => return Err(error(ErrorKind::TypeError, format!("unknown operator: INTEGER {operator} INTEGER"))),
+        };
+        if overflowed && self.config.overflow == IntegerOverflow::Promote {
+            return Evaluator::eval_big_integer_infix_expression(operator, &BigInt::from(left), &BigInt::from(right));
+        }
+        Ok(Object::Integer(value))
+    }
+
+    fn eval_big_integer_infix_expression(operator: TokenType, left: &BigInt, right: &BigInt) -> EvalResult {
+        if let Some(value) = Evaluator::compare(operator, Some(left.cmp(right))) {
+            return Ok(value);
+        }
+        let value = match operator {
+            TokenType::Plus => left + right,
+            TokenType::Minus => left - right,
+            TokenType::Asterisk => left * right,
+            TokenType::Slash => match left.div_rem(right) {
+                Some((quotient, _)) => quotient,
+                None => return Err(error(ErrorKind::ArithmeticError, "division by zero".to_string())),
+            },
+            _ => return Err(error(ErrorKind::TypeError, format!("unknown operator: INTEGER {operator} INTEGER"))),
+        };
+        Ok(Object::from_big_integer(value))
+    }
+
+    fn eval_double_infix_expression(operator: TokenType, left: f64, right: f64) -> EvalResult {
+        if let Some(value) = Evaluator::compare(operator, left.partial_cmp(&right)) {
+            return Ok(value);
+        }
+        let value = match operator {
+            TokenType::Plus => left + right,
+            TokenType::Minus => left - right,
+            TokenType::Asterisk => left * right,
+            TokenType::Slash => left / right,
+            _ => return Err(error(ErrorKind::TypeError, format!("unknown operator: DOUBLE {operator} DOUBLE"))),
+        };
+        Ok(Object::Double(value))
+    }
+}
+
+fn to_big_integer(value: Object) -> BigInt {
+    match value {
+        Object::Integer(value) => BigInt::from(value),
+        Object::BigInteger(value) => value,
+        other => unreachable!("{} is not an integer", other.type_name()),
+    }
+}
+
+/// Same as `compare_integer_double` for integers beyond the i64 range.
+fn compare_big_integer_double(integer: &BigInt, double: f64) -> Option<Ordering> {
+    if double.is_nan() {
+        return None;
+    }
+    if double.is_infinite() {
+        return Some(if double > 0.0 { Ordering::Less } else { Ordering::Greater });
+    }
+    let integral = double.trunc();
+    match integer.cmp(&BigInt::from_f64(integral)?) {
+        Ordering::Equal => 0.0.partial_cmp(&(double - integral)),
+        ordering => Some(ordering),
+    }
+}
+
+/// Orders an integer and a double exactly, which casting the integer to a double does not do
+/// above 2^53. Returns None when the double is NaN.
+fn compare_integer_double(integer: i64, double: f64) -> Option<Ordering> {
+    const TWO_POW_63: f64 = 9_223_372_036_854_775_808.0;
+    if double.is_nan() {
+        None
+    } else if double >= TWO_POW_63 {
+        Some(Ordering::Less)
+    } else if double < -TWO_POW_63 {
+        Some(Ordering::Greater)
+    } else {
+        // the double is in the i64 range here, so its integral part converts exactly
+        let integral = double.trunc();
+        match integer.cmp(&(integral as i64)) {
+            Ordering::Equal => 0.0.partial_cmp(&(double - integral)),
+            ordering => Some(ordering),
+        }
+    }
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+    use crate::evaluator::{Config, Evaluator, IntegerOverflow, Limits, Truthiness};
+    use crate::lexer::Lexer;
+    use crate::object::{ErrorKind, Object, RuntimeError};
+    use crate::parser::Parser;
+
+    fn eval(input: &str) -> Result<Object, RuntimeError> {
+        eval_with_config(input, Config::default())
+    }
+
+    fn eval_with_config(input: &str, config: Config) -> Result<Object, RuntimeError> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "parser has errors: {:?}", parser.errors());
+        Evaluator::with_config(config).eval(&program)
+    }
+
+    fn assert_evaluates_to(tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match eval(input) {
+                Ok(value) => assert_eq!(expected, value.to_string(), "wrong value for {input}"),
+                Err(e) => panic!("unexpected error for {input}: {e}"),
+            }
+        }
+    }
+
+    fn assert_fails_with(tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match eval(input) {
+                Ok(value) => panic!("expected an error for {input}, got {value}"),
+                Err(e) => assert_eq!(expected, e.message, "wrong error for {input}"),
+            }
+        }
+    }
+
+    fn assert_results_with_config(config: Config, tests: Vec<(&str, Result<&str, &str>)>) {
+        for (input, expected) in tests {
+            match (expected, eval_with_config(input, config.clone())) {
+                (Ok(expected), Ok(value)) => assert_eq!(expected, value.to_string(), "wrong value for {input}"),
+                (Err(expected), Err(e)) => assert_eq!(expected, e.message, "wrong error for {input}"),
+                (_, result) => panic!("unexpected result for {input}: {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_eval_expressions() {
+        assert_evaluates_to(vec![
+            ("5", "5"),
+            ("-5 + 10 * 2", "15"),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50"),
+            ("2.5 * 2.0", "5.0"),
+            ("1 < 2 == true", "true"),
+            ("!5", "false"),
+            ("!!true", "true"),
+            ("if (1 > 2) { 10 }", "null"),
+            ("if (1 < 2) { 10 } else { 20 }", "10"),
+            ("var x = 1; x = x + 1; x", "2"),
+            ("var add = func(a, b) { return a + b; }; add(2, 3)", "5"),
+            ("var f = func() { 1; return 2; 3 }; f()", "2"),
+        ]);
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        assert_fails_with(vec![
+            ("5 + true", "type mismatch: INTEGER + BOOLEAN"),
+            ("-true", "unknown operator: -BOOLEAN"),
+            ("true + false", "unknown operator: BOOLEAN + BOOLEAN"),
+            ("foobar", "identifier not found: foobar"),
+            ("y = 1", "identifier not found: y"),
+            ("1 / 0", "division by zero"),
+            ("func(x) { x }()", "wrong number of arguments: expected 1, got 0"),
+            ("5()", "not a function: INTEGER"),
+        ]);
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let tests: Vec<(&str, ErrorKind)> = vec![
+            ("1 + true", ErrorKind::TypeError),
+            ("missing", ErrorKind::NameError),
+            ("func(a) { a }()", ErrorKind::ArgumentError),
+            ("[1][1]", ErrorKind::IndexError),
+            (r#"{"a": 1}["b"]"#, ErrorKind::KeyError),
+            (r#"int("x")"#, ErrorKind::ValueError),
+            ("1 / 0", ErrorKind::ArithmeticError),
+            ("break;", ErrorKind::SyntaxError),
+        ];
+
+        for (input, expected) in tests {
+            match eval(input) {
+                Ok(value) => panic!("expected an error for {input}, got {value}"),
+                Err(e) => assert_eq!(expected, e.kind, "wrong kind for {input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_traces() {
+        let tests: Vec<(&str, &str)> = vec![
+            ("1 +\n  true", "TypeError: type mismatch: INTEGER + BOOLEAN at 1:3"),
+            ("var x = [1, 2];\nx[5]", "IndexError: index out of bounds: index 5, length 2 at 2:2"),
+            ("len(1)", "TypeError: len: argument must be STRING, ARRAY or HASH, got INTEGER at 1:4"),
+            ("var f = func(a) { a };\nf()", "ArgumentError: wrong number of arguments: expected 1, got 0 at 2:2"),
+            ("5()", "TypeError: not a function: INTEGER at 1:2"),
+            ("for (x in 5) { }", "TypeError: not iterable: INTEGER"),
+            (
+                "var inner = func(x) { x / 0 };\nvar outer = func() { inner(1) };\nouter()",
+                "ArithmeticError: division by zero at 1:25\n    in inner called at 2:27\n    in outer called at 3:6",
+            ),
+            (
+                "var apply = func(f) { f() };\napply(func() { missing })",
+                "NameError: identifier not found: missing at 2:16\n    in <anonymous> called at 1:24\n    in apply called at 2:6",
+            ),
+            (
+                "var f = func(n) { if (n == 0) { n.key } else { f(n - 1) } };\nf(2)",
+                "TypeError: property access not supported: INTEGER at 1:34\n    in f called at 1:49\n    in f called at 1:49\n    in f called at 2:2",
+            ),
+        ];
+
+        for (input, expected) in tests {
+            match eval(input) {
+                Ok(value) => panic!("expected an error for {input}, got {value}"),
+                Err(e) => assert_eq!(expected, e.traceback(), "wrong traceback for {input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_deep_recursion() {
+        assert_evaluates_to(vec![
+            ("var count = func(n) { if (n == 0) { 0 } else { 1 + count(n - 1) } }; count(100000)", "100000"),
+            (
+                "var sum = func(a) { if (len(a) == 0) { 0 } else { first(a) + sum(rest(a)) } }; sum(range(3000))",
+                "4498500",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_max_call_depth() {
+        let config = Config { max_call_depth: 50, ..Config::default() };
+        let forever = "var f = func(n) { f(n + 1) };\n";
+        let tests: Vec<(&str, &str)> = vec![
+            ("var f = func(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(49)", "0"),
+            ("var f = func(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(50)", "maximum call depth of 50 exceeded"),
+            ("var f = func(n) { f(n + 1) }; try { f(0) } catch (e) { e.kind }", "StackOverflow"),
+            ("var f = func(n) { f(n + 1) }; try { f(0) } catch (e) { 1 } f(0)", "maximum call depth of 50 exceeded"),
+        ];
+        for (input, expected) in tests {
+            match eval_with_config(input, config.clone()) {
+                Ok(value) => assert_eq!(expected, value.to_string(), "wrong value for {input}"),
+                Err(e) => assert_eq!(expected, e.message, "wrong error for {input}"),
+            }
+        }
+
+        let input = format!("{forever}f(0)");
+        let traceback = eval_with_config(&input, config).unwrap_err().traceback();
+        let mut expected = vec!["StackOverflow: maximum call depth of 50 exceeded at 1:20"];
+        expected.extend(["    in f called at 1:20"; 10]);
+        expected.push("    ... 30 more calls ...");
+        expected.extend(["    in f called at 1:20"; 9]);
+        expected.push("    in f called at 2:2");
+        assert_eq!(expected.join("\n"), traceback);
+    }
+
+    #[test]
+    fn test_nested_callbacks() {
+        let nested = "var f = func(n) { if (n == 0) { 0 } else { map([n], func(x) { 1 + f(x - 1) })[0] } };\n";
+        assert_results_with_config(Config::default(), vec![
+            (&format!("{nested}f(99)"), Ok("99")),
+            (&format!("{nested}f(100)"), Err("maximum depth of 100 builtins calling back exceeded")),
+            (&format!("{nested}try {{ f(1000) }} catch (e) {{ e.kind }}"), Ok("StackOverflow")),
+        ]);
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = |limits: Limits| Config { limits, ..Config::default() };
+        let steps = limits(Limits { max_steps: Some(5), ..Limits::default() });
+        assert_results_with_config(steps.clone(), vec![
+            ("1 + 2 + 3", Ok("6")),
+            ("1 + 2 + 3 + 4", Err("step limit of 5 exceeded")),
+            ("for (;;) { }", Err("step limit of 5 exceeded")),
+        ]);
+        let forever = limits(Limits { max_steps: Some(100_000), ..Limits::default() });
+        assert_results_with_config(forever, vec![
+            ("var i = 0; while (i < 1000) { i = i + 1 } i", Ok("1000")),
+            ("while (true) { }", Err("step limit of 100000 exceeded")),
+            ("try { while (true) { } } catch (e) { 1 }", Err("step limit of 100000 exceeded")),
+            ("try { 1 } catch (e) { while (true) { } }", Ok("1")),
+            ("try { throw 1 } catch (e) { while (true) { } } finally { 2 }", Err("step limit of 100000 exceeded")),
+            ("var f = func() { return f() }; f()", Err("step limit of 100000 exceeded")),
+        ]);
+
+        let timeout = limits(Limits { timeout: Some(Duration::from_millis(50)), ..Limits::default() });
+        let start = Instant::now();
+        assert_results_with_config(timeout, vec![
+            ("for (;;) { }", Err("time limit of 50ms exceeded")),
+            ("try { for (;;) { } } catch (e) { e }", Err("time limit of 50ms exceeded")),
+        ]);
+        assert!(start.elapsed() < Duration::from_secs(10));
+
+        let heap = limits(Limits { max_heap_bytes: Some(1_000_000), ..Limits::default() });
+        assert_results_with_config(heap.clone(), vec![
+            (r#"len(repeat("ab", 1000))"#, Ok("2000")),
+            (r#"repeat("ab", 1000000)"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"var s = "x"; while (true) { s = s + s }"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"var s = "x"; while (true) { s = "${s}${s}" }"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"var s = "x"; while (true) { s = join([s, s], "") }"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"var s = "x"; while (true) { s = replace(s, "x", "xx") }"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"var a = [1]; while (true) { a = [a, a]; str(a) }"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"split(repeat("a", 500000), "")"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"len(join(split(repeat("ab", 1000), "b"), "-"))"#, Ok("2000")),
+            ("range(100000000)", Err("heap limit of 1000000 bytes exceeded")),
+            (r#"len(upper(repeat("ŉ", 400000)))"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"len(lower(repeat("İ", 400000)))"#, Err("heap limit of 1000000 bytes exceeded")),
+            (r#"upper(repeat("ŉ", 2))"#, Ok("ʼNʼN")),
+            (r#"len(trim(repeat(" a ", 1000)))"#, Ok("2998")),
+            (r#"var a = []; while (true) { push(a, "abcdefgh") }"#, Err("heap limit of 1000000 bytes exceeded")),
+            ("var a = []; for (var i = 0; i < 1000; i = i + 1) { push(a, i) } len(a)", Ok("1000")),
+        ]);
+        // a step that does not divide the span still counts the last element
+        let fitting = 1_000_000 / size_of::<Object>();
+        let (end, length) = (2 * fitting, fitting.to_string());
+        assert_results_with_config(heap.clone(), vec![
+            (&format!("len(range(0, {end}, 2))"), Ok(&length)),
+            (&format!("len(range(0, {}, 2))", end + 1), Err("heap limit of 1000000 bytes exceeded")),
+            (&format!("len(range({}, 0, -2))", end + 1), Err("heap limit of 1000000 bytes exceeded")),
+        ]);
+        let e = eval_with_config("var a = []; while (true) { push(a, [1, 2, 3]) }", heap).unwrap_err();
+        assert_eq!(ErrorKind::MemoryLimitExceeded, e.kind);
+
+        let no_io = limits(Limits { disable_io: true, ..Limits::default() });
+        assert_results_with_config(no_io.clone(), vec![
+            (r#"print("a")"#, Err("print: I/O is disabled")),
+            ("try { println(1) } catch (e) { 2 }", Err("println: I/O is disabled")),
+            ("len([1, 2])", Ok("2")),
+        ]);
+        assert_eq!(ErrorKind::PermissionError, eval_with_config("print(1)", no_io).unwrap_err().kind);
+        assert_eq!(ErrorKind::StepLimitExceeded, eval_with_config("for (;;) { }", steps).unwrap_err().kind);
+    }
+
+    #[test]
+    fn test_tail_calls() {
+        assert_evaluates_to(vec![
+            (
+                "var count = func(n, total) { if (n == 0) { return total } return count(n - 1, total + 1) };
+                count(1000000, 0)",
+                "1000000",
+            ),
+            (
+                "var is_even = func(n) { if (n == 0) { return true } return is_odd(n - 1) };
+                var is_odd = func(n) { if (n == 0) { return false } return is_even(n - 1) };
+                is_even(1000001)",
+                "false",
+            ),
+            ("var f = func(x) { return len(x) }; f([1, 2, 3])", "3"),
+            ("var g = func(x) { x * 2 }; var f = func(x) { return g(x) + 1 }; f(4)", "9"),
+            ("return len([1])", "1"),
+            (
+                "var fail = func() { 1 / 0 }; var f = func() { try { return fail() } catch (e) { e.kind } }; f()",
+                "ArithmeticError",
+            ),
+            (
+                "var log = []; var g = func() { push(log, \"call\"); 1 };
+                var f = func() { try { return g() } finally { push(log, \"finally\") } };
+                f(); log",
+                "[\"call\", \"finally\"]",
+            ),
+        ]);
+
+        let config = Config { max_call_depth: 10, ..Config::default() };
+        let input = "var f = func(n) { if (n == 0) { return \"done\" } return f(n - 1) }; f(1000)";
+        assert_eq!("done", eval_with_config(input, config).unwrap().to_string());
+
+        let tests: Vec<(&str, &str)> = vec![
+            (
+                "var inner = func(x) { x / 0 };\nvar outer = func() { return inner(1) };\nouter()",
+                "ArithmeticError: division by zero at 1:25\n    in inner called at 2:34",
+            ),
+            ("var f = func() { return f(1) };\nf()", "ArgumentError: wrong number of arguments: expected 0, got 1 at 1:26"),
+            ("var f = func() { return len(1) };\nf()", "TypeError: len: argument must be STRING, ARRAY or HASH, got INTEGER at 1:28"),
+        ];
+        for (input, expected) in tests {
+            match eval(input) {
+                Ok(value) => panic!("expected an error for {input}, got {value}"),
+                Err(e) => assert_eq!(expected, e.traceback(), "wrong traceback for {input}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_try_catch() {
+        assert_evaluates_to(vec![
+            (r#"try { {"a": 1}["b"] } catch (e) { e.message }"#, r#"key not found: "b""#),
+            (r#"try { int("x") } catch (e) { e.kind }"#, "ValueError"),
+            ("try { 1 / 0 } catch (e) { e }", "ArithmeticError: division by zero"),
+            ("try { 1 / 0 } catch (e) { type_of(e) }", "error"),
+            ("try { 1 / 0 } catch (e) { e.value }", "null"),
+            ("try { 5 } catch (e) { 6 }", "5"),
+            ("var x = 1; try { x = 2; missing; x = 3; } catch (e) { x } ", "2"),
+            ("try { try { 1 / 0 } catch (e) { missing } } catch (e) { e.message }", "identifier not found: missing"),
+            ("try { try { 1 / 0 } catch (e) { throw e } } catch (e) { e.kind }", "ArithmeticError"),
+            ("try { 1 / 0 } catch (e) { e?.unknown ?? \"fallback\" }", "fallback"),
+        ]);
+        assert_fails_with(vec![
+            ("try { 1 / 0 } catch (e) { e.unknown }", "unknown property 'unknown' of ERROR"),
+            ("try { 1 / 0 } catch (e) { } e", "identifier not found: e"),
+        ]);
+    }
+
+    #[test]
+    fn test_throw() {
+        assert_evaluates_to(vec![
+            (r#"try { throw "oops"; } catch (e) { e.message }"#, "oops"),
+            (r#"try { throw "oops"; } catch (e) { e.kind }"#, "Error"),
+            (r#"try { throw {"code": 42}; } catch (e) { e.value.code }"#, "42"),
+            ("try { throw 7; } catch (e) { e.value + 1 }", "8"),
+            (
+                "var check = func(n) { if (n < 0) { throw \"negative\" } n }; try { check(-1) } catch (e) { e.trace }",
+                "Error: negative at 1:36\n    in check called at 1:71",
+            ),
+        ]);
+        assert_fails_with(vec![(r#"throw "uncaught""#, "uncaught")]);
+    }
+
+    #[test]
+    fn test_finally() {
+        assert_evaluates_to(vec![
+            ("var log = []; try { push(log, 1) } finally { push(log, 2) } log", "[1, 2]"),
+            ("var log = []; try { try { 1 / 0 } finally { push(log, 1) } } catch (e) { push(log, 2) } log", "[1, 2]"),
+            ("var log = []; try { 1 / 0 } catch (e) { push(log, 1) } finally { push(log, 2) } log", "[1, 2]"),
+            ("var f = func() { try { return 1 } finally { 2 } }; f()", "1"),
+            // leaving finally early replaces what was pending
+            ("var f = func() { try { return 1 } finally { return 2 } }; f()", "2"),
+            ("var f = func() { try { 1 / 0 } finally { return 3 } }; f()", "3"),
+            ("var f = func() { try { return 1 } finally { throw \"late\" } }; try { f() } catch (e) { e.message }", "late"),
+            ("var f = func() { try { throw \"first\" } catch (e) { throw \"second\" } finally { } }; try { f() } catch (e) { e.message }", "second"),
+            ("var n = 0; while (true) { try { n = n + 1; if (n > 20) { break } } finally { n = n + 10 } } n", "33"),
+            ("var i = 0; var s = 0; while (i < 3) { i = i + 1; try { continue } finally { s = s + i } } s", "6"),
+        ]);
+        assert_fails_with(vec![("try { missing } finally { }", "identifier not found: missing")]);
+    }
+
+    #[test]
+    fn test_result_propagation() {
+        assert_evaluates_to(vec![
+            ("var f = func() { var x = Ok(5)?; x + 1 }; f()", "6"),
+            (r#"var f = func() { var x = Err("bad")?; x + 1 }; f()"#, r#"Err("bad")"#),
+            (
+                r#"var parse = func(s) { if (s == "") { Err("empty") } else { Ok(len(s)) } };
+                var total = func(a, b) { Ok(parse(a)? + parse(b)?) };
+                [total("ab", "c"), total("ab", "")]"#,
+                r#"[Ok(3), Err("empty")]"#,
+            ),
+            (r#"var f = func() { for (x in [Ok(1), Err(2), Ok(3)]) { x?; } Ok(0) }; f()"#, "Err(2)"),
+            (r#"var f = func(r) { r?["name"] }; f(Ok({"name": "n"}))"#, "n"),
+            // `?.` is optional member access, unwrapping before a member access needs parentheses
+            (r#"var f = func(r) { (r?).name }; f(Ok({"name": "n"}))"#, "n"),
+            (r#"Err("top")?; 5"#, r#"Err("top")"#),
+        ]);
+        assert_fails_with(vec![("var f = func() { 5? }; f()", "? operator requires RESULT, got INTEGER")]);
+    }
+
+    #[test]
+    fn test_numeric_promotion() {
+        assert_evaluates_to(vec![
+            ("1 + 2.5", "3.5"),
+            ("2.5 + 1", "3.5"),
+            ("3 - 0.5", "2.5"),
+            ("2 * 1.5", "3.0"),
+            ("7 / 2", "3"),
+            ("-7 / 2", "-3"),
+            ("7 / 2.0", "3.5"),
+            ("7.0 / 2", "3.5"),
+            ("1 / 0.0", "inf"),
+            ("-1 / 0.0", "-inf"),
+            ("0.0 / 0.0", "NaN"),
+            ("1 == 1.0", "true"),
+            ("1.0 != 1", "false"),
+            ("2 > 1.5", "true"),
+            ("1.5 >= 2", "false"),
+            ("1 < 1.5", "true"),
+            ("9007199254740993 == 9007199254740992.0", "false"),
+            ("9007199254740993 > 9007199254740992.0", "true"),
+            ("9223372036854775807 < 9223372036854775808.0", "true"),
+            ("-9223372036854775807 - 1 == -9223372036854775808.0", "true"),
+        ]);
+    }
+
+    #[test]
+    fn test_nan_comparisons() {
+        assert_evaluates_to(vec![
+            ("var nan = 0.0 / 0.0; nan == nan", "false"),
+            ("var nan = 0.0 / 0.0; nan != nan", "true"),
+            ("var nan = 0.0 / 0.0; nan < 1", "false"),
+            ("var nan = 0.0 / 0.0; nan > 1", "false"),
+            ("var nan = 0.0 / 0.0; nan <= nan", "false"),
+            ("var nan = 0.0 / 0.0; 1 >= nan", "false"),
+            ("var nan = 0.0 / 0.0; 1.0 == nan", "false"),
+            ("var nan = 0.0 / 0.0; 1 != nan", "true"),
+        ]);
+    }
+
+    #[test]
+    fn test_big_integer_promotion() {
+        assert_evaluates_to(vec![
+            ("9223372036854775807 - 1 + 1", "9223372036854775807"),
+            ("-9223372036854775807 - 1", "-9223372036854775808"),
+            ("9223372036854775807 + 1", "9223372036854775808"),
+            ("-9223372036854775807 - 2", "-9223372036854775809"),
+            ("4611686018427387904 * 2", "9223372036854775808"),
+            ("var min = -9223372036854775807 - 1; min / -1", "9223372036854775808"),
+            ("var min = -9223372036854775807 - 1; -min", "9223372036854775808"),
+            ("-9223372036854775808", "-9223372036854775808"),
+            ("123456789012345678901234567890", "123456789012345678901234567890"),
+            ("123_456_789_012_345_678_901_234_567_890 * 10", "1234567890123456789012345678900"),
+            ("100000000000000000000 - 99999999999999999999", "1"),
+            ("(9223372036854775807 + 1) - 1 == 9223372036854775807", "true"),
+            ("100000000000000000000 / 3", "33333333333333333333"),
+            ("-100000000000000000000 / 7", "-14285714285714285714"),
+            ("100000000000000000000 > 99", "true"),
+            ("-100000000000000000000 < -99", "true"),
+            ("100000000000000000000 == 100000000000000000000", "true"),
+            ("100000000000000000000 == 100000000000000000000.0", "true"),
+            ("100000000000000000001 > 100000000000000000000.0", "true"),
+            ("100000000000000000000 + 0.5", "1e20"),
+            ("-(9223372036854775807 + 1)", "-9223372036854775808"),
+            (
+                "var f = 1; for (var i = 1; i <= 25; i = i + 1) { f = f * i; } f",
+                "15511210043330985984000000",
+            ),
+            ("{100000000000000000000: 1}[100000000000000000000]", "1"),
+            ("if (100000000000000000000) { 1 } else { 2 }", "1"),
+        ]);
+        assert_fails_with(vec![
+            ("100000000000000000000 / 0", "division by zero"),
+            ("1 / 0", "division by zero"),
+            ("0 / 0", "division by zero"),
+            ("[1, 2][100000000000000000000]", "index out of bounds: index 100000000000000000000, length 2"),
+            ("[1, 2][:100000000000000000000]", "slice out of bounds: 0:100000000000000000000, length 2"),
+            ("100000000000000000000 + true", "type mismatch: INTEGER + BOOLEAN"),
+        ]);
+    }
+
+    #[test]
+    fn test_wrapping_overflow() {
+        let wrapping = Config { overflow: IntegerOverflow::Wrapping, ..Default::default() };
+        let tests: Vec<(&str, Result<&str, &str>)> = vec![
+            ("9223372036854775807 + 1", Ok("-9223372036854775808")),
+            ("-9223372036854775807 - 3", Ok("9223372036854775806")),
+            ("4611686018427387904 * 2", Ok("-9223372036854775808")),
+            ("var min = -9223372036854775807 - 1; min / -1", Ok("-9223372036854775808")),
+            ("var min = -9223372036854775807 - 1; -min", Ok("-9223372036854775808")),
+            ("1 / 0", Err("division by zero")),
+            ("100000000000000000000 + 1", Ok("100000000000000000001")),
+        ];
+        assert_results_with_config(wrapping, tests);
+    }
+
+    #[test]
+    fn test_block_scoping() {
+        assert_evaluates_to(vec![
+            ("var x = 1; if (true) { var x = 2; } x", "1"),
+            ("var x = 1; if (true) { x = 2; } x", "2"),
+        ]);
+        assert_fails_with(vec![("if (true) { var y = 2; } y", "identifier not found: y")]);
+    }
+
+    #[test]
+    fn test_while_loops() {
+        assert_evaluates_to(vec![
+            ("var i = 0; while (i < 10) { i = i + 1; } i", "10"),
+            ("var i = 0; while (false) { i = i + 1; } i", "0"),
+            ("var i = 0; while (true) { i = i + 1; if (i == 5) { break; } } i", "5"),
+            (
+                "var i = 0; var sum = 0; while (i < 10) { i = i + 1; if (i == 5) { continue; } sum = sum + i; } sum",
+                "50",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_for_loops() {
+        assert_evaluates_to(vec![
+            ("var sum = 0; for (var i = 1; i <= 4; i = i + 1) { sum = sum + i; } sum", "10"),
+            ("var i = 0; for (; i < 3;) { i = i + 1; } i", "3"),
+            ("var i = 0; for (;;) { i = i + 1; if (i > 7) { break; } } i", "8"),
+            ("var n = 0; for (var i = 0; i < 10; i = i + 1) { if (i < 8) { continue; } n = n + 1; } n", "2"),
+        ]);
+        assert_fails_with(vec![
+            ("for (var i = 0; i < 3; i = i + 1) { } i", "identifier not found: i"),
+            ("for (x in 5) { }", "not iterable: INTEGER"),
+        ]);
+    }
+
+    #[test]
+    fn test_arrays() {
+        assert_evaluates_to(vec![
+            ("[]", "[]"),
+            ("[1, 2 * 2, 3 + 3]", "[1, 4, 6]"),
+            ("[1, 2, 3][0]", "1"),
+            ("[1, 2, 3][2]", "3"),
+            ("var i = 0; [1][i]", "1"),
+            ("var a = [1, 2, 3]; a[0] + a[1] + a[2]", "6"),
+            ("[1, 2, 3][-1]", "3"),
+            ("[1, 2, 3][-3]", "1"),
+            ("[[1, 2], [3]][0][1]", "2"),
+        ]);
+        assert_fails_with(vec![
+            ("[1, 2, 3][3]", "index out of bounds: index 3, length 3"),
+            ("[1, 2, 3][-4]", "index out of bounds: index -4, length 3"),
+            ("[][0]", "index out of bounds: index 0, length 0"),
+            ("[1][true]", "array index must be INTEGER, got BOOLEAN"),
+            ("1[0]", "index operator not supported: INTEGER"),
+        ]);
+    }
+
+    #[test]
+    fn test_array_slices() {
+        assert_evaluates_to(vec![
+            ("[1, 2, 3, 4][1:3]", "[2, 3]"),
+            ("[1, 2, 3, 4][:2]", "[1, 2]"),
+            ("[1, 2, 3, 4][2:]", "[3, 4]"),
+            ("[1, 2, 3, 4][:]", "[1, 2, 3, 4]"),
+            ("[1, 2, 3, 4][-2:]", "[3, 4]"),
+            ("[1, 2, 3, 4][1:-1]", "[2, 3]"),
+            ("[1, 2, 3, 4][2:2]", "[]"),
+            ("[1, 2, 3, 4][4:]", "[]"),
+        ]);
+        assert_fails_with(vec![
+            ("[1, 2, 3][1:5]", "slice out of bounds: 1:5, length 3"),
+            ("[1, 2, 3][2:1]", "slice out of bounds: 2:1, length 3"),
+            ("[1, 2, 3][-5:]", "slice out of bounds: -5:3, length 3"),
+            ("[1, 2, 3][true:]", "slice bounds must be INTEGER, got BOOLEAN"),
+            ("5[1:2]", "slice operator not supported: INTEGER"),
+        ]);
+    }
+
+    #[test]
+    fn test_string_operations() {
+        assert_evaluates_to(vec![
+            (r#""foo" + "bar""#, "foobar"),
+            (r#""" + """#, ""),
+            (r#"var s = "a"; s = s + "b"; s + "c""#, "abc"),
+            (r#""abc" == "abc""#, "true"),
+            (r#""abc" != "abd""#, "true"),
+            (r#""abc" < "abd""#, "true"),
+            (r#""ab" < "abc""#, "true"),
+            (r#""b" > "abc""#, "true"),
+            (r#""Z" < "a""#, "true"),
+            (r#""é" > "z""#, "true"),
+            (r#""a" <= "a""#, "true"),
+            (r#""b" >= "c""#, "false"),
+            (r#""héllo"[1]"#, "é"),
+            (r#""héllo"[-1]"#, "o"),
+            (r#""日本語"[2]"#, "語"),
+            (r#""héllo"[1:3]"#, "él"),
+            (r#""héllo"[:-1]"#, "héll"),
+            (r#""héllo"[5:]"#, ""),
+        ]);
+        assert_fails_with(vec![
+            (r#""a" - "b""#, "unknown operator: STRING - STRING"),
+            (r#""a" + 1"#, "type mismatch: STRING + INTEGER"),
+            (r#""héllo"[5]"#, "index out of bounds: index 5, length 5"),
+            (r#""abc"[-4]"#, "index out of bounds: index -4, length 3"),
+            (r#""abc"["a"]"#, "string index must be INTEGER, got STRING"),
+            (r#""abc"[1:4]"#, "slice out of bounds: 1:4, length 3"),
+        ]);
+    }
+
+    #[test]
+    fn test_string_interpolation() {
+        assert_evaluates_to(vec![
+            (r#"var name = "Ann"; var count = 2; "Hello ${name}, you have ${count + 1} items""#, "Hello Ann, you have 3 items"),
+            (r#""${1}${2}""#, "12"),
+            (r#""${"nested ${1 + 1}"}!""#, "nested 2!"),
+            (r#"var h = {"k": [1, "two"]}; "${h["k"]} ${h}""#, r#"[1, "two"] {"k": [1, "two"]}"#),
+            (r#""${func(x) { x * 2 }(21)}""#, "42"),
+            (r#""${[1, 2, 3][1:]}""#, "[2, 3]"),
+            (r#""${null} ${true} ${1.5}""#, "null true 1.5"),
+            (r#""\${literal}""#, "${literal}"),
+            (r#""cost: $5""#, "cost: $5"),
+        ]);
+        assert_fails_with(vec![(r#""${missing}""#, "identifier not found: missing")]);
+    }
+
+    #[test]
+    fn test_format_specifiers() {
+        assert_evaluates_to(vec![
+            (r#""[${3.14159:.2}]""#, "[3.14]"),
+            (r#""[${2:.3}]""#, "[2.000]"),
+            (r#""[${2.5:.0}]""#, "[2]"),
+            (r#""[${42:6}]""#, "[    42]"),
+            (r#""[${42:<6}]""#, "[42    ]"),
+            (r#""[${42:^6}]""#, "[  42  ]"),
+            (r#""[${"ab":5}]""#, "[ab   ]"),
+            (r#""[${"ab":>5}]""#, "[   ab]"),
+            (r#""[${"ab":^5}]""#, "[ ab  ]"),
+            (r#""[${-42:06}]""#, "[-00042]"),
+            (r#""[${3.14159:08.3}]""#, "[0003.142]"),
+            (r#""[${"abcdef":.3}]""#, "[abc]"),
+            (r#""[${"abcdef":5.2}]""#, "[ab   ]"),
+            (r#""[${"ab":05}]""#, "[ab   ]"),
+            (r#""[${12345:3}]""#, "[12345]"),
+            (r#""[${"héé":>4}]""#, "[ héé]"),
+            (r#""[${0.0 / 0.0:05}]""#, "[  NaN]"),
+            (r#""[${100000000000000000000:.1}]""#, "[100000000000000000000.0]"),
+        ]);
+    }
+
+    #[test]
+    fn test_hashes() {
+        assert_evaluates_to(vec![
+            ("{}", "{}"),
+            (r#"{"name": "x", 1: true, false: 2.5}"#, r#"{"name": "x", 1: true, false: 2.5}"#),
+            (r#"{"b": 1, "a": 2, "c": 3}"#, r#"{"b": 1, "a": 2, "c": 3}"#),
+            (r#"{"a": 1, "b": 2, "a": 3}"#, r#"{"a": 3, "b": 2}"#),
+            (r#"var key = "k"; {key: [1, "v"]}"#, r#"{"k": [1, "v"]}"#),
+            (r#"{"one": 1, "two": 2}["two"]"#, "2"),
+            ("{1: 10, 2: 20}[1]", "10"),
+            ("{true: 10, false: 20}[false]", "20"),
+            (r#"{"nested": {"a": [1, 2]}}["nested"]["a"][-1]"#, "2"),
+            (r#""plain""#, "plain"),
+        ]);
+        assert_fails_with(vec![
+            (r#"{"a": 1}["b"]"#, r#"key not found: "b""#),
+            ("{1: 1}[1.5]", "unusable as hash key: DOUBLE"),
+            ("{[1]: 1}", "unusable as hash key: ARRAY"),
+            ("{func() { 1 }: 1}", "unusable as hash key: FUNCTION"),
+        ]);
+    }
+
+    #[test]
+    fn test_hash_iteration_order() {
+        assert_evaluates_to(vec![
+            (
+                r#"
+                var hash = {"z": 1, "a": 2, "m": 3, 10: 4, true: 5};
+                var order = 0;
+                for (key in hash) {
+                    order = order * 10 + hash[key];
+                }
+                order
+                "#,
+                "12345",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_null() {
+        assert_evaluates_to(vec![
+            ("null", "null"),
+            ("var x = null; x", "null"),
+            ("null == null", "true"),
+            ("null != null", "false"),
+            ("1 == null", "false"),
+            ("null != [1]", "true"),
+            ("!null", "true"),
+            ("if (false) { 1 }", "null"),
+            ("if (null) { 1 } else { 2 }", "2"),
+        ]);
+        assert_fails_with(vec![
+            ("null + 1", "type mismatch: NULL + INTEGER"),
+            ("-null", "unknown operator: -NULL"),
+            ("null < null", "unknown operator: NULL < NULL"),
+        ]);
+    }
+
+    #[test]
+    fn test_loose_truthiness() {
+        assert_evaluates_to(vec![
+            ("!false", "true"),
+            ("!null", "true"),
+            ("!0", "true"),
+            ("!-1", "false"),
+            ("!0.0", "true"),
+            ("!0.5", "false"),
+            (r#"!"""#, "true"),
+            (r#"!"a""#, "false"),
+            ("![]", "true"),
+            ("![0]", "false"),
+            ("!{}", "true"),
+            (r#"!{"a": null}"#, "false"),
+            ("!func() { }", "false"),
+            ("if (0) { 1 } else { 2 }", "2"),
+            ("if ([1]) { 1 } else { 2 }", "1"),
+            ("var n = 3; var steps = 0; while (n) { n = n - 1; steps = steps + 1; } steps", "3"),
+            ("var n = 0; for (var i = 2; i; i = i - 1) { n = n + 1; } n", "2"),
+        ]);
+    }
+
+    #[test]
+    fn test_strict_truthiness() {
+        let strict = Config { truthiness: Truthiness::Strict, ..Default::default() };
+        let tests: Vec<(&str, Result<&str, &str>)> = vec![
+            ("!true", Ok("false")),
+            ("if (1 < 2) { 1 } else { 2 }", Ok("1")),
+            ("var i = 0; while (i < 2) { i = i + 1; } i", Ok("2")),
+            ("!0", Err("strict truthiness: expected BOOLEAN, got INTEGER")),
+            ("!null", Err("strict truthiness: expected BOOLEAN, got NULL")),
+            ("if (1) { 1 }", Err("strict truthiness: expected BOOLEAN, got INTEGER")),
+            (r#"if ("") { 1 }"#, Err("strict truthiness: expected BOOLEAN, got STRING")),
+            ("while ([]) { }", Err("strict truthiness: expected BOOLEAN, got ARRAY")),
+            ("for (; {};) { }", Err("strict truthiness: expected BOOLEAN, got HASH")),
+        ];
+        assert_results_with_config(strict, tests);
+    }
+
+    #[test]
+    fn test_function_results() {
+        assert_evaluates_to(vec![
+            ("func() { }()", "null"),
+            ("func() { 5 }()", "5"),
+            ("func() { var x = 5; }()", "null"),
+            ("func() { var x = 5; x = 6 }()", "6"),
+            ("func() { while (false) { } }()", "null"),
+            ("func() { return; }()", "null"),
+            ("func() { if (false) { return 1; } }()", "null"),
+            ("func() { if (true) { return 1; } 2 }()", "1"),
+        ]);
+    }
+
+    #[test]
+    fn test_closures() {
+        assert_evaluates_to(vec![
+            ("var counter = func() { var n = 0; func() { n = n + 1; n } }; var c = counter(); c(); c(); c()", "3"),
+            ("var counter = func() { var n = 0; func() { n = n + 1; n } }; var c = counter(); c(); counter()()", "1"),
+            (
+                "var pair = func() { var n = 0; [func() { n = n + 1 }, func() { n }] };
+                var p = pair(); p[0](); p[0](); p[1]()",
+                "2",
+            ),
+            ("var x = 1; var get = func() { x }; x = 2; get()", "2"),
+            ("var set = func() { late = 5 }; var late = 0; set(); late", "5"),
+            ("var adder = func(a) { func(b) { func(c) { a + b + c } } }; adder(1)(2)(3)", "6"),
+            ("var x = 1; var f = func(x) { func() { x = x + 1; x } }; f(10)(); x", "1"),
+            ("var f = func() { var x = 10; var bump = func() { x = x + 1 }; bump(); bump(); x }; f()", "12"),
+            (
+                "var fs = []; for (var i = 0; i < 3; i = i + 1) { push(fs, func() { i }) } [fs[0](), fs[1](), fs[2]()]",
+                "[0, 1, 2]",
+            ),
+            ("var fs = []; for (x in [1, 2, 3]) { push(fs, func() { x }) } [fs[0](), fs[2]()]", "[1, 3]"),
+            (
+                "var fs = []; var j = 0; while (j < 3) { var k = j; push(fs, func() { k }); j = j + 1 } [fs[0](), fs[2]()]",
+                "[0, 2]",
+            ),
+            (
+                "var total = 0; var fs = []; for (x in [1, 2, 3]) { push(fs, func() { total = total + x }) }
+                fs[0](); fs[1](); fs[2](); total",
+                "6",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_member_access() {
+        assert_evaluates_to(vec![
+            (r#"{"name": "x"}.name"#, "x"),
+            (r#"var user = {"address": {"city": "Sofia"}}; user.address.city"#, "Sofia"),
+            (r#"var user = {"greet": func(name) { name }}; user.greet("hi")"#, "hi"),
+            (r#"{"name": "x"}?.name"#, "x"),
+            ("null?.name", "null"),
+            (r#"{"name": "x"}?.age"#, "null"),
+            (r#"var user = {"address": null}; user.address?.city"#, "null"),
+            (r#"var user = {}; user?.address?.city"#, "null"),
+        ]);
+        assert_fails_with(vec![
+            (r#"{"name": "x"}.age"#, r#"key not found: "age""#),
+            ("null.name", "cannot access property 'name' of null"),
+            ("var n = 5; n.name", "property access not supported: INTEGER"),
+            ("var n = 5; n?.name", "property access not supported: INTEGER"),
+        ]);
+    }
+
+    #[test]
+    fn test_null_coalescing() {
+        assert_evaluates_to(vec![
+            ("null ?? 1", "1"),
+            ("2 ?? 1", "2"),
+            ("false ?? 1", "false"),
+            ("null ?? null ?? 3", "3"),
+            ("null ?? null", "null"),
+            (r#"var config = {}; config?.port ?? 8080"#, "8080"),
+            (r#"var config = {"port": 80}; config?.port ?? 8080"#, "80"),
+            ("1 ?? undefined_variable", "1"),
+            ("var x = null; x = x ?? 5; x", "5"),
+        ]);
+        assert_fails_with(vec![("null ?? undefined_variable", "identifier not found: undefined_variable")]);
+    }
+
+    #[test]
+    fn test_for_in_loops() {
+        assert_evaluates_to(vec![
+            ("var sum = 0; for (x in [1, 2, 3]) { sum = sum + x; } sum", "6"),
+            ("var sum = 0; for (x in []) { sum = sum + 1; } sum", "0"),
+            ("var last = 0; for (x in [1, 2, 3]) { if (x == 2) { break; } last = x; } last", "1"),
+            ("var sum = 0; for (x in [1, 2, 3]) { if (x == 2) { continue; } sum = sum + x; } sum", "4"),
+            (
+                r#"
+                var count = 0;
+                rows: for (row in [[1, 2], [3, 4]]) {
+                    for (cell in row) {
+                        if (cell == 2) { continue rows; }
+                        count = count + cell;
+                    }
+                }
+                count
+                "#,
+                "8",
+            ),
+        ]);
+        assert_fails_with(vec![("for (x in [1]) { } x", "identifier not found: x")]);
+    }
+
+    #[test]
+    fn test_labeled_loops() {
+        assert_evaluates_to(vec![
+            (
+                r#"
+                var count = 0;
+                outer: for (var i = 0; i < 3; i = i + 1) {
+                    for (var j = 0; j < 3; j = j + 1) {
+                        if (j == 1) { continue outer; }
+                        count = count + 1;
+                    }
+                }
+                count
+                "#,
+                "3",
+            ),
+            (
+                r#"
+                var count = 0;
+                outer: while (true) {
+                    while (true) {
+                        count = count + 1;
+                        break outer;
+                    }
+                    count = 100;
+                }
+                count
+                "#,
+                "1",
+            ),
+        ]);
+        assert_fails_with(vec![
+            ("break;", "break outside of a loop"),
+            ("func() { continue; }()", "continue outside of a loop"),
+            ("while (true) { break outer; }", "unknown loop label 'outer'"),
+            ("outer: while (true) { func() { break outer; }(); }", "unknown loop label 'outer'"),
+        ]);
+    }
+
+    #[test]
+    fn test_loop_variables_captured_by_closures() {
+        assert_evaluates_to(vec![
+            (
+                r#"
+                var getters = [func() { 0 }, func() { 0 }, func() { 0 }];
+                var sum = 0;
+                for (getter in [1, 2, 3]) {
+                    var f = func() { getter };
+                    if (getter == 1) { getters = [f, getters[1], getters[2]]; }
+                    if (getter == 2) { getters = [getters[0], f, getters[2]]; }
+                    if (getter == 3) { getters = [getters[0], getters[1], f]; }
+                }
+                getters[0]() * 100 + getters[1]() * 10 + getters[2]()
+                "#,
+                "123",
+            ),
+            (
+                r#"
+                var first = 0;
+                var second = 0;
+                for (var i = 0; i < 2; i = i + 1) {
+                    if (i == 0) { first = func() { i }; } else { second = func() { i }; }
+                }
+                first() * 10 + second()
+                "#,
+                "1",
+            ),
+            (
+                r#"
+                var counter = 0;
+                for (var i = 0; i < 3; i = i + 1) {
+                    counter = func() { i = i + 10; i };
+                }
+                counter() + counter()
+                "#,
+                "34",
+            ),
+        ]);
+    }
+}
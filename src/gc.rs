@@ -0,0 +1,358 @@
+//! Reference counting frees values as soon as nothing uses them, except for arrays, hashes, functions
+//! and scopes referencing each other in a cycle, like a function stored in the scope it captured.
+//! The heap keeps track of all of these containers and regularly looks for groups of them that nothing
+//! outside of the group references. Such groups are garbage: they are emptied, which breaks the cycles
+//! and lets reference counting free them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::rc::{Rc, Weak};
+use std::time::{Duration, Instant};
+use crate::environment::{Env, Environment};
+use crate::object::{Function, Hash, Object};
+
+/// Totals since the evaluator was created.
+#[derive(PartialEq, Debug, Clone, Default)]
+pub struct GcStats {
+    pub collections: usize,
+    pub objects_freed: usize,
+    /// Estimated from the size of the containers and of the values they held.
+    pub bytes_freed: usize,
+    pub pause_time: Duration,
+    /// Containers still alive after the last collection.
+    pub live_objects: usize,
+}
+
+enum Tracked {
+    Array(Weak<RefCell<Vec<Object>>>),
+    Hash(Weak<RefCell<Hash>>),
+    Function(Weak<Function>),
+    Environment(Weak<RefCell<Environment>>),
+}
+
+/// A tracked container kept alive for the duration of a collection.
+enum Node {
+    Array(Rc<RefCell<Vec<Object>>>),
+    Hash(Rc<RefCell<Hash>>),
+    Function(Rc<Function>),
+    Environment(Env),
+}
+
+fn address<T>(rc: &Rc<T>) -> usize {
+    Rc::as_ptr(rc) as *const u8 as usize
+}
+
+/// Collects the containers `object` references directly, looking inside results.
+fn references(object: &Object, found: &mut Vec<usize>) {
+    match object {
+        Object::Array(elements) => found.push(address(elements)),
+        Object::Hash(hash) => found.push(address(hash)),
+        Object::Function(function) => found.push(address(function)),
+        Object::Ok(value) | Object::Err(value) => references(value, found),
+        _ => {}
+    }
+}
+
+/// Estimated bytes used by `object` outside of the container holding it, the containers it references
+/// are counted separately.
+fn value_size(object: &Object) -> usize {
+    match object {
+        Object::String(value) => value.capacity(),
+        Object::Ok(value) | Object::Err(value) => size_of::<Object>() + value_size(value),
+        _ => 0,
+    }
+}
+
+impl Tracked {
+    fn upgrade(&self) -> Option<Node> {
+        match self {
+            Tracked::Array(weak) => weak.upgrade().map(Node::Array),
+            Tracked::Hash(weak) => weak.upgrade().map(Node::Hash),
+            Tracked::Function(weak) => weak.upgrade().map(Node::Function),
+            Tracked::Environment(weak) => weak.upgrade().map(Node::Environment),
+        }
+    }
+
+    fn is_alive(&self) -> bool {
+        match self {
+            Tracked::Array(weak) => weak.strong_count() > 0,
+            Tracked::Hash(weak) => weak.strong_count() > 0,
+            Tracked::Function(weak) => weak.strong_count() > 0,
+            Tracked::Environment(weak) => weak.strong_count() > 0,
+        }
+    }
+}
+
+impl Node {
+    fn address(&self) -> usize {
+        match self {
+            Node::Array(rc) => address(rc),
+            Node::Hash(rc) => address(rc),
+            Node::Function(rc) => address(rc),
+            Node::Environment(rc) => address(rc),
+        }
+    }
+
+    fn strong_count(&self) -> usize {
+        match self {
+            Node::Array(rc) => Rc::strong_count(rc),
+            Node::Hash(rc) => Rc::strong_count(rc),
+            Node::Function(rc) => Rc::strong_count(rc),
+            Node::Environment(rc) => Rc::strong_count(rc),
+        }
+    }
+
+    /// Estimated bytes used by the container and the values it holds directly.
+    fn size(&self) -> usize {
+        match self {
+            Node::Array(elements) => {
+                let elements = elements.try_borrow().map(|elements| {
+                    elements.capacity() * size_of::<Object>() + elements.iter().map(value_size).sum::<usize>()
+                });
+                size_of::<RefCell<Vec<Object>>>() + elements.unwrap_or(0)
+            }
+            Node::Hash(hash) => {
+                let entries = hash.try_borrow().map(|hash| {
+                    hash.entries().iter().map(|(key, value)| 3 * size_of::<Object>() + value_size(key) + value_size(value)).sum()
+                });
+                size_of::<RefCell<Hash>>() + entries.unwrap_or(0)
+            }
+            Node::Function(function) => size_of::<Function>() + function.parameters.len() * size_of::<String>(),
+            Node::Environment(env) => {
+                let values = env.try_borrow().map(|env| {
+                    env.values().map(|value| size_of::<String>() + size_of::<Object>() + value_size(value)).sum()
+                });
+                size_of::<RefCell<Environment>>() + values.unwrap_or(0)
+            }
+        }
+    }
+
+    /// The containers this one references, none if it is being modified right now, which can only
+    /// happen to a container in use.
+    fn references(&self) -> Option<Vec<usize>> {
+        let mut found = vec![];
+        match self {
+            Node::Array(elements) => {
+                for element in elements.try_borrow().ok()?.iter() {
+                    references(element, &mut found);
+                }
+            }
+            Node::Hash(hash) => {
+                for (key, value) in hash.try_borrow().ok()?.entries() {
+                    references(key, &mut found);
+                    references(value, &mut found);
+                }
+            }
+            Node::Function(function) => found.push(address(&function.env)),
+            Node::Environment(env) => {
+                let env = env.try_borrow().ok()?;
+                for value in env.values() {
+                    references(value, &mut found);
+                }
+                if let Some(outer) = env.outer() {
+                    found.push(address(outer));
+                }
+            }
+        }
+        Some(found)
+    }
+
+    /// Empties the container, moving what it held to `garbage`. Returns the estimated bytes freed.
+    fn clear(&self, garbage: &mut Vec<Object>, scopes: &mut Vec<Env>) -> usize {
+        let bytes = self.size();
+        match self {
+            Node::Array(elements) => garbage.extend(std::mem::take(&mut *elements.borrow_mut())),
+            Node::Hash(hash) => {
+                for (key, value) in hash.borrow_mut().clear() {
+                    garbage.push(key);
+                    garbage.push(value);
+                }
+            }
+            // a function cannot be changed, clearing the scope it captured is enough to break its cycles
+            Node::Function(_) => {}
+            Node::Environment(env) => {
+                let (values, outer) = env.borrow_mut().clear();
+                garbage.extend(values);
+                scopes.extend(outer);
+            }
+        }
+        bytes
+    }
+}
+
+/// Registry of the containers created by an evaluator, see the module documentation.
+pub struct Heap {
+    tracked: Vec<Tracked>,
+    /// Containers created since the last collection.
+    allocations: usize,
+    /// Collections happen after this many allocations, or after as many as there were live containers
+    /// after the last collection if that is more, so that large heaps are not scanned over and over.
+    threshold: usize,
+    stats: GcStats,
+}
+
+impl Heap {
+    pub fn new(threshold: usize) -> Heap {
+        Heap { tracked: vec![], allocations: 0, threshold, stats: GcStats::default() }
+    }
+
+    pub fn stats(&self) -> &GcStats {
+        &self.stats
+    }
+
+    fn track(&mut self, tracked: Tracked) {
+        self.tracked.push(tracked);
+        self.allocations += 1;
+        if self.allocations >= self.threshold.max(self.stats.live_objects) {
+            self.collect();
+        }
+    }
+
+    pub fn new_array(&mut self, elements: Vec<Object>) -> Object {
+        let elements = Rc::new(RefCell::new(elements));
+        self.track(Tracked::Array(Rc::downgrade(&elements)));
+        Object::Array(elements)
+    }
+
+    pub fn new_hash(&mut self, hash: Hash) -> Object {
+        let hash = Rc::new(RefCell::new(hash));
+        self.track(Tracked::Hash(Rc::downgrade(&hash)));
+        Object::Hash(hash)
+    }
+
+    pub fn new_function(&mut self, function: Function) -> Object {
+        let function = Rc::new(function);
+        self.track(Tracked::Function(Rc::downgrade(&function)));
+        Object::Function(function)
+    }
+
+    /// A scope enclosed in `outer`, or a top-level scope.
+    pub fn new_environment(&mut self, outer: Option<Env>) -> Env {
+        let env = match outer {
+            Some(outer) => Environment::new_enclosed(outer),
+            None => Environment::new(),
+        };
+        self.track(Tracked::Environment(Rc::downgrade(&env)));
+        env
+    }
+
+    pub fn copy_environment(&mut self, env: &Env) -> Env {
+        let copy = env.borrow().copy();
+        self.track(Tracked::Environment(Rc::downgrade(&copy)));
+        copy
+    }
+
+    /// Estimated bytes used by the live containers and the values they hold, scanning all of them.
+    pub fn live_bytes(&self) -> usize {
+        self.tracked.iter().filter_map(Tracked::upgrade).map(|node| node.size()).sum()
+    }
+
+    /// Frees the unreachable cycles, returns the number of containers freed.
+    ///
+    /// Whatever references a container from outside of the heap, like a variable of the Rust code
+    /// running the evaluator, keeps it and everything it references alive. These references are not
+    /// known directly: they are what remains of a reference count after subtracting the references
+    /// from the other containers.
+    pub fn collect(&mut self) -> usize {
+        let start = Instant::now();
+        let nodes: Vec<Node> = self.tracked.iter().filter_map(Tracked::upgrade).collect();
+        let positions: HashMap<usize, usize> = nodes.iter().enumerate().map(|(i, node)| (node.address(), i)).collect();
+
+        // the count held by `nodes` itself is left out from the start
+        let mut outside: Vec<usize> = nodes.iter().map(|node| node.strong_count() - 1).collect();
+        let mut children: Vec<Vec<usize>> = Vec::with_capacity(nodes.len());
+        for (i, node) in nodes.iter().enumerate() {
+            match node.references() {
+                Some(references) => {
+                    let references: Vec<usize> = references.iter().filter_map(|address| positions.get(address).copied()).collect();
+                    for &child in &references {
+                        outside[child] -= 1;
+                    }
+                    children.push(references);
+                }
+                None => {
+                    // what a busy container references is unknown, so it and its children are kept
+                    outside[i] += 1;
+                    children.push(vec![]);
+                }
+            }
+        }
+
+        let mut reachable: Vec<bool> = outside.iter().map(|&count| count > 0).collect();
+        let mut pending: Vec<usize> = (0..nodes.len()).filter(|&i| reachable[i]).collect();
+        while let Some(i) = pending.pop() {
+            for &child in &children[i] {
+                if !reachable[child] {
+                    reachable[child] = true;
+                    pending.push(child);
+                }
+            }
+        }
+
+        let mut garbage = vec![];
+        let mut scopes = vec![];
+        let mut freed = 0;
+        for (node, _) in nodes.iter().zip(&reachable).filter(|(_, &reachable)| !reachable) {
+            self.stats.bytes_freed += node.clear(&mut garbage, &mut scopes);
+            freed += 1;
+        }
+        // the contents are dropped only once every container has been emptied
+        drop(garbage);
+        drop(scopes);
+        drop(nodes);
+
+        self.tracked.retain(Tracked::is_alive);
+        self.allocations = 0;
+        self.stats.collections += 1;
+        self.stats.objects_freed += freed;
+        self.stats.live_objects = self.tracked.len();
+        self.stats.pause_time += start.elapsed();
+        freed
+    }
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use crate::gc::Heap;
+    use crate::object::{Hash, Object};
+
+    #[test]
+    fn test_collects_cycles() {
+        let mut heap = Heap::new(1000);
+        let array = heap.new_array(vec![]);
+        if let Object::Array(elements) = &array {
+            elements.borrow_mut().push(array.clone());
+        }
+        let weak = match &array {
+            Object::Array(elements) => std::rc::Rc::downgrade(elements),
+            _ => unreachable!(),
+        };
+
+        assert_eq!(0, heap.collect());
+        drop(array);
+        assert!(weak.upgrade().is_some());
+        assert_eq!(1, heap.collect());
+        assert!(weak.upgrade().is_none());
+        assert_eq!(2, heap.stats().collections);
+        assert_eq!(1, heap.stats().objects_freed);
+        assert_eq!(0, heap.stats().live_objects);
+    }
+
+    #[test]
+    fn test_keeps_values_referenced_from_outside() {
+        let mut heap = Heap::new(1000);
+        let inner = heap.new_array(vec![Object::Integer(1)]);
+        let mut hash = Hash::new();
+        hash.insert(Object::String("inner".to_string()), inner).unwrap();
+        let outer = heap.new_hash(hash);
+        let env = heap.new_environment(None);
+        env.borrow_mut().set("outer", outer);
+
+        assert_eq!(0, heap.collect());
+        assert_eq!(r#"{"inner": [1]}"#, env.borrow().get("outer").unwrap().to_string());
+        assert_eq!(3, heap.stats().live_objects);
+    }
+}
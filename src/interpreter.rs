@@ -0,0 +1,327 @@
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::path::Path;
+use std::rc::Rc;
+use crate::evaluator::{Config, Evaluator};
+use crate::lexer::Lexer;
+use crate::module;
+use crate::native::{Native, NativeFunction};
+use crate::object::{ErrorKind, Object, RuntimeError};
+use crate::parser::Parser;
+use crate::prelude;
+use crate::resolver::{Diagnostic, Resolver};
+use crate::value::IntoValue;
+
+/// Why source code given to the interpreter did not run to completion.
+#[derive(Debug)]
+pub enum Error {
+    /// The source does not parse, holds the messages of the parser.
+    Syntax(Vec<String>),
+    /// The resolver found errors, the warnings it found are included. Nothing was run.
+    Resolve(Vec<Diagnostic>),
+    Runtime(RuntimeError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Syntax(errors) => {
+                let lines: Vec<String> = errors.iter().map(|error| format!("Parser error: {error}")).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Error::Resolve(diagnostics) => {
+                let lines: Vec<String> = diagnostics.iter().map(Diagnostic::to_string).collect();
+                write!(f, "{}", lines.join("\n"))
+            }
+            Error::Runtime(e) => write!(f, "{}", e.traceback()),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<RuntimeError> for Error {
+    fn from(e: RuntimeError) -> Self {
+        Error::Runtime(e)
+    }
+}
+
+/// Runs scripts for a host application. Top-level variables persist from one call of `eval` to the
+/// next, like the lines typed into the REPL.
+pub struct Interpreter {
+    evaluator: Evaluator,
+    resolver: Resolver,
+    /// Warnings of the last program given to `eval`.
+    warnings: Vec<Diagnostic>,
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Interpreter::new()
+    }
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        Interpreter::with_config(Config::default())
+    }
+
+    pub fn with_config(mut config: Config) -> Interpreter {
+        // the limits are meant for the scripts of the host, the prelude always loads
+        let limits = std::mem::take(&mut config.limits);
+        let prelude = config.prelude;
+        let mut interpreter = Interpreter {
+            evaluator: Evaluator::with_config(config),
+            resolver: Resolver::new(),
+            warnings: vec![],
+        };
+        if prelude {
+            for (name, source) in prelude::FILES {
+                let name = format!("prelude/{name}");
+                let program = module::parse(source, &[]);
+                if let Err(e) = program.and_then(|program| interpreter.evaluator.load_prelude(&name, &program)) {
+                    panic!("the prelude file {name} is broken: {}", e.traceback());
+                }
+            }
+            for name in interpreter.evaluator.shared_names() {
+                interpreter.resolver.declare_global(&name);
+            }
+        }
+        interpreter.evaluator.set_limits(limits);
+        interpreter
+    }
+
+    /// Redirects what `print` and `println` write, stdout by default.
+    pub fn set_output(&mut self, output: Box<dyn Write>) {
+        self.evaluator.set_output(output);
+    }
+
+    pub fn evaluator(&self) -> &Evaluator {
+        &self.evaluator
+    }
+
+    /// Parses, checks and runs `source`, returning the value of its last statement. Modules are imported
+    /// relative to the current directory.
+    pub fn eval(&mut self, source: &str) -> Result<Object, Error> {
+        self.run(source, None)
+    }
+
+    /// Runs the script in the file at `path`, modules are imported relative to it.
+    pub fn eval_file(&mut self, path: impl AsRef<Path>) -> Result<Object, Error> {
+        let path = path.as_ref();
+        let source = std::fs::read_to_string(path).map_err(|e| {
+            RuntimeError::new(ErrorKind::IoError, format!("unable to read {}: {e}", path.display()))
+        })?;
+        self.run(&source, Some(path))
+    }
+
+    fn run(&mut self, source: &str, path: Option<&Path>) -> Result<Object, Error> {
+        self.warnings.clear();
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program();
+        if !parser.errors().is_empty() {
+            return Err(Error::Syntax(parser.errors().to_vec()));
+        }
+        let declared = self.resolver.global_count();
+        let resolved = self.resolver.resolve(&program);
+        if resolved.has_errors() {
+            return Err(Error::Resolve(resolved.diagnostics));
+        }
+        self.warnings = resolved.diagnostics;
+        let result = match path {
+            Some(path) => self.evaluator.eval_file(&program, path),
+            None => self.evaluator.eval(&program),
+        };
+        if result.is_err() {
+            let evaluator = &self.evaluator;
+            self.resolver.forget_globals(declared, |name| evaluator.global(name).is_some());
+        }
+        Ok(result?)
+    }
+
+    /// The warnings found in the source given to the last successful `eval`.
+    pub fn warnings(&self) -> &[Diagnostic] {
+        &self.warnings
+    }
+
+    /// Calls the function or builtin bound to the top-level variable `name`.
+    pub fn call_function(&mut self, name: &str, arguments: Vec<Object>) -> Result<Object, Error> {
+        let function = self.evaluator.global(name).ok_or_else(|| {
+            RuntimeError::new(ErrorKind::NameError, format!("identifier not found: {name}"))
+        })?;
+        Ok(self.evaluator.apply(function, arguments)?)
+    }
+
+    /// Makes a Rust closure callable from scripts as the top-level function `name`. Its arguments are
+    /// converted with `FromValue`, a wrong number or type of arguments fails the call with a script
+    /// error. It returns a value or a `Result`, whose error is raised in the script.
+    pub fn register<Args>(&mut self, name: &str, function: impl NativeFunction<Args> + 'static) {
+        self.set_global(name, Object::Native(Rc::new(Native::new(name, function))));
+    }
+
+    /// The top-level variable `name`, or the builtin of that name.
+    pub fn get_global(&self, name: &str) -> Option<Object> {
+        self.evaluator.global(name)
+    }
+
+    /// Declares or replaces the top-level variable `name`, scripts run afterwards and the modules they
+    /// import can use it.
+    pub fn set_global(&mut self, name: &str, value: impl IntoValue) {
+        self.resolver.declare_global(name);
+        self.evaluator.set_global(name, value.into_value());
+    }
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::collections::{BTreeMap, HashMap};
+    use crate::evaluator::{Config, Limits};
+    use crate::interpreter::{Error, Interpreter};
+    use crate::object::{ErrorKind, Object};
+    use crate::value::{FromValue, IntoValue};
+
+    #[test]
+    fn test_eval() {
+        let mut interpreter = Interpreter::new();
+        assert_eq!("3", interpreter.eval("1 + 2").unwrap().to_string());
+        interpreter.eval("var double = func(x) { x * 2 }").unwrap();
+        assert_eq!("8", interpreter.eval("double(4)").unwrap().to_string());
+        assert!(interpreter.warnings().is_empty());
+
+        interpreter.eval("var f = func(unused) { 1 }").unwrap();
+        assert_eq!("Warning: unused parameter 'unused' at 1:14", interpreter.warnings()[0].to_string());
+
+        match interpreter.eval("var = 1") {
+            Err(e @ Error::Syntax(_)) => assert!(e.to_string().starts_with("Parser error: "), "{e}"),
+            other => panic!("expected a syntax error, got {other:?}"),
+        }
+        match interpreter.eval("missing + 1") {
+            Err(e @ Error::Resolve(_)) => assert_eq!("Error: identifier not found: missing at 1:1", e.to_string()),
+            other => panic!("expected a resolve error, got {other:?}"),
+        }
+        match interpreter.eval("1 / 0") {
+            Err(Error::Runtime(e)) => assert_eq!(ErrorKind::ArithmeticError, e.kind),
+            other => panic!("expected a runtime error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        let tests = vec![
+            ("1.", "invalid number literal at 1:1"),
+            ("println(5.x)", "invalid number literal at 1:9"),
+            ("var a = 1__", "invalid number literal at 1:9"),
+            ("var b = ٣", "unexpected character '٣' at 1:9"),
+        ];
+        for (input, expected) in tests {
+            match Interpreter::new().eval(input) {
+                Err(Error::Syntax(errors)) => assert_eq!(expected, errors[0], "wrong error for {input}"),
+                other => panic!("expected a syntax error for {input}, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failed_declarations() {
+        let mut interpreter = Interpreter::new();
+        assert!(matches!(interpreter.eval("var a = 1; var b = 1 / 0; var c = 2"), Err(Error::Runtime(_))));
+        assert_eq!("1", interpreter.eval("a").unwrap().to_string());
+        for name in ["b", "c"] {
+            match interpreter.eval(name) {
+                Err(e @ Error::Resolve(_)) => assert_eq!(format!("Error: identifier not found: {name} at 1:1"), e.to_string()),
+                other => panic!("expected a resolve error for {name}, got {other:?}"),
+            }
+        }
+        assert_eq!("3", interpreter.eval("var b = 3; b").unwrap().to_string());
+    }
+
+    #[test]
+    fn test_globals() {
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("limit", 10);
+        interpreter.set_global("name", "host");
+        interpreter.set_global("ratios", vec![0.5, 1.5]);
+        interpreter.set_global("missing", None::<i64>);
+        let greeting = interpreter.eval(r#""${name} ${limit + 2} ${ratios[1]}""#).unwrap();
+        assert_eq!("host 12 1.5", greeting.to_string());
+        assert_eq!("true", interpreter.eval("missing == null").unwrap().to_string());
+
+        interpreter.eval("var total = limit * 3").unwrap();
+        assert_eq!(30, i64::from_value(interpreter.get_global("total").unwrap()).unwrap());
+        interpreter.set_global("limit", 1);
+        assert_eq!("1", interpreter.eval("limit").unwrap().to_string());
+        assert!(interpreter.get_global("len").is_some());
+        assert!(interpreter.get_global("nothing").is_none());
+    }
+
+    #[test]
+    fn test_call_function() {
+        let mut interpreter = Interpreter::new();
+        interpreter.eval("var add = func(a, b) { a + b }; var fail = func() { throw \"no\" }").unwrap();
+        let sum = interpreter.call_function("add", vec![2.into_value(), 3.into_value()]).unwrap();
+        assert_eq!(5, i64::from_value(sum).unwrap());
+        let length = interpreter.call_function("len", vec!["abc".into_value()]).unwrap();
+        assert_eq!("3", length.to_string());
+
+        let errors = [
+            ("add", vec![1.into_value()], "wrong number of arguments: expected 2, got 1"),
+            ("fail", vec![], "no"),
+            ("nothing", vec![], "identifier not found: nothing"),
+        ];
+        for (name, arguments, expected) in errors {
+            match interpreter.call_function(name, arguments) {
+                Err(Error::Runtime(e)) => assert_eq!(expected, e.message, "wrong error for {name}"),
+                other => panic!("expected an error for {name}, got {other:?}"),
+            }
+        }
+        interpreter.set_global("answer", 42);
+        match interpreter.call_function("answer", vec![]) {
+            Err(Error::Runtime(e)) => assert_eq!("not a function: INTEGER", e.message),
+            other => panic!("expected an error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_call_function_limits() {
+        let limits = Limits { max_steps: Some(1000), ..Limits::default() };
+        let mut interpreter = Interpreter::with_config(Config { limits, ..Config::default() });
+        interpreter.eval("var spin = func() { while (true) { } }; var one = func() { 1 }").unwrap();
+        match interpreter.call_function("spin", vec![]) {
+            Err(Error::Runtime(e)) => assert_eq!(ErrorKind::StepLimitExceeded, e.kind),
+            other => panic!("expected the step limit, got {other:?}"),
+        }
+        // every call gets the whole budget again
+        for _ in 0..10 {
+            assert_eq!("1", interpreter.call_function("one", vec![]).unwrap().to_string());
+        }
+    }
+
+    #[test]
+    fn test_conversions() {
+        let mut interpreter = Interpreter::new();
+        let value = interpreter.eval(r#"{"a": [1, 2], "b": []}"#).unwrap();
+        let map = BTreeMap::<String, Vec<i64>>::from_value(value.clone()).unwrap();
+        assert_eq!(BTreeMap::from([("a".to_string(), vec![1, 2]), ("b".to_string(), vec![])]), map);
+        assert_eq!(r#"{"a": [1, 2], "b": []}"#, map.into_value().to_string());
+        assert_eq!(2, HashMap::<String, Vec<i32>>::from_value(value).unwrap().len());
+
+        assert_eq!(2.0, f64::from_value(Object::Integer(2)).unwrap());
+        assert_eq!(Some("x".to_string()), Option::<String>::from_value("x".into_value()).unwrap());
+        assert_eq!(None, Option::<String>::from_value(Object::Null).unwrap());
+        assert!(bool::from_value(true.into_value()).unwrap());
+
+        let errors = [
+            (i64::from_value("1".into_value()).unwrap_err(), "expected INTEGER, got STRING"),
+            (i32::from_value(Object::Integer(1 << 40)).unwrap_err(), "integer out of range: 1099511627776"),
+            (u32::from_value(Object::Integer(-1)).unwrap_err(), "integer out of range: -1"),
+            (Vec::<String>::from_value(interpreter.eval(r#"["a", 1]"#).unwrap()).unwrap_err(), "expected STRING, got INTEGER"),
+            (BTreeMap::<String, i64>::from_value(interpreter.eval("{1: 1}").unwrap()).unwrap_err(), "expected STRING, got INTEGER"),
+            (String::from_value(Object::Null).unwrap_err(), "expected STRING, got NULL"),
+        ];
+        for (e, expected) in errors {
+            assert_eq!(expected, e.message);
+        }
+    }
+}
@@ -1,24 +1,43 @@
-use crate::token::{lookup_identifier, Token, TokenType};
+use crate::token::{lookup_identifier, Span, Token, TokenType};
 
 pub struct Lexer {
     input: Vec<char>,
     curr_position: usize,
     next_position: usize,
     processed_char: char,
+    line: usize,
+    column: usize,
+    token_start: Span,
+    /// Bracket depth inside each interpolation `${...}` currently open, innermost last.
+    interpolations: Vec<usize>,
 }
 
 impl Lexer {
     pub fn new(input: &str) -> Lexer {
-        let lexer: Lexer = Lexer {
+        Lexer {
             input: input.chars().collect(),
             curr_position: 0,
             next_position: 1,
             processed_char: Default::default(),
-        };
-        lexer
+            line: 1,
+            column: 0,
+            token_start: Span::default(),
+            interpolations: vec![],
+        }
+    }
+
+    /// Where the token last returned by `next_token` starts.
+    pub fn span(&self) -> Span {
+        self.token_start
     }
 
     fn read_char(&mut self) {
+        if self.processed_char == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
         if self.curr_position == self.input.len() {
             self.processed_char = '\0';
         } else {
@@ -39,7 +58,8 @@ impl Lexer {
     pub fn next_token(&mut self) -> Token {
         self.read_char();
         self.skip_whitespace();
-        let token = match self.processed_char {
+        self.token_start = Span { line: self.line, column: self.column };
+        match self.processed_char {
             '=' => {
                 if self.peek_char() == '=' {
                     self.read_char();
@@ -68,12 +88,38 @@ impl Lexer {
                     Token { t_type: TokenType::LessThan, literal: self.processed_char.to_string() }
                 }
             }
-            '(' => Token { t_type: TokenType::OpenParenthesis, literal: self.processed_char.to_string() },
-            ')' => Token { t_type: TokenType::CloseParenthesis, literal: self.processed_char.to_string() },
-            '{' => Token { t_type: TokenType::OpenBrace, literal: self.processed_char.to_string() },
-            '}' => Token { t_type: TokenType::CloseBrace, literal: self.processed_char.to_string() },
+            '(' | '{' | '[' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth += 1;
+                }
+                let t_type = match self.processed_char {
+                    '(' => TokenType::OpenParenthesis,
+                    '{' => TokenType::OpenBrace,
+                    _ => TokenType::OpenBracket,
+                };
+                Token { t_type, literal: self.processed_char.to_string() }
+            }
+            // the brace closing an interpolation resumes the string it is embedded in
+            '}' if self.interpolations.last() == Some(&0) => {
+                self.interpolations.pop();
+                self.read_string(true)
+            }
+            ')' | '}' | ']' => {
+                if let Some(depth) = self.interpolations.last_mut() {
+                    *depth = depth.saturating_sub(1);
+                }
+                let t_type = match self.processed_char {
+                    ')' => TokenType::CloseParenthesis,
+                    '}' => TokenType::CloseBrace,
+                    _ => TokenType::CloseBracket,
+                };
+                Token { t_type, literal: self.processed_char.to_string() }
+            }
             ',' => Token { t_type: TokenType::Comma, literal: self.processed_char.to_string() },
             ';' => Token { t_type: TokenType::Semicolon, literal: self.processed_char.to_string() },
+            // a colon outside of any brackets in an interpolation starts its format specifier
+            ':' if self.interpolations.last() == Some(&0) => self.read_format_spec(),
+            ':' => Token { t_type: TokenType::Colon, literal: self.processed_char.to_string() },
             '!' => {
                 if self.peek_char() == '=' {
                     self.read_char();
@@ -82,26 +128,34 @@ impl Lexer {
                     Token { t_type: TokenType::Bang, literal: self.processed_char.to_string() }
                 }
             }
+            '.' => Token { t_type: TokenType::Dot, literal: self.processed_char.to_string() },
+            '?' => {
+                if self.peek_char() == '.' {
+                    self.read_char();
+                    Token { t_type: TokenType::OptionalDot, literal: "?.".to_string() }
+                } else if self.peek_char() == '?' {
+                    self.read_char();
+                    Token { t_type: TokenType::NullCoalescing, literal: "??".to_string() }
+                } else {
+                    Token { t_type: TokenType::Question, literal: self.processed_char.to_string() }
+                }
+            }
+            '"' if self.peek_char() == '"' && self.peek_second_char() == '"' => self.read_multi_line_string(),
+            '"' => self.read_string(false),
+            'r' if matches!(self.peek_char(), '"' | '#') => self.read_raw_string(),
             '\0' => Token { t_type: TokenType::EndOfFile, literal: "".to_string() },
             _ => {
-                return if Lexer::is_letter(self.processed_char) {
+                if Lexer::is_letter(self.processed_char) {
                     let literal: String = self.read_identifier();
                     let t_type: TokenType = lookup_identifier(&literal);
                     Token { t_type, literal }
                 } else if Lexer::is_digit(self.processed_char) {
-                    let literal: String = self.read_number();
-                    let t_type: TokenType = if literal.contains(".") {
-                        TokenType::Double
-                    } else {
-                        TokenType::Integer
-                    };
-                    Token { t_type, literal }
+                    self.read_number()
                 } else {
                     Token { t_type: TokenType::Illegal, literal: self.processed_char.to_string() }
-                };
+                }
             }
-        };
-        token
+        }
     }
 
     fn skip_whitespace(&mut self) {
@@ -124,27 +178,172 @@ impl Lexer {
         identifier
     }
 
+    /// Reads string characters up to the closing quote or up to the `${` opening an interpolation.
+    /// `continued` is set when resuming a string after an interpolation, so the fragment is a
+    /// middle or end fragment instead of a whole string or a start fragment.
+    fn read_string(&mut self, continued: bool) -> Token {
+        let mut string = String::new();
+        loop {
+            if self.peek_char() == '\0' {
+                return Lexer::unterminated_string();
+            }
+            self.read_char();
+            match self.processed_char {
+                '"' => {
+                    let t_type = if continued { TokenType::StringEnd } else { TokenType::String };
+                    return Token { t_type, literal: string };
+                }
+                '$' if self.peek_char() == '{' => {
+                    self.read_char();
+                    self.interpolations.push(0);
+                    let t_type = if continued { TokenType::StringMiddle } else { TokenType::StringStart };
+                    return Token { t_type, literal: string };
+                }
+                '\\' => {
+                    self.read_char();
+                    match self.processed_char {
+                        '\0' => return Lexer::unterminated_string(),
+                        other => string.push(unescape(other)),
+                    }
+                }
+                other => string.push(other),
+            }
+        }
+    }
+
+    /// Reads the format specifier of an interpolation up to its closing brace, the colon is the processed char.
+    fn read_format_spec(&mut self) -> Token {
+        let mut spec = String::new();
+        while self.peek_char() != '}' {
+            if self.peek_char() == '\0' {
+                return Lexer::unterminated_string();
+            }
+            self.read_char();
+            spec.push(self.processed_char);
+        }
+        Token { t_type: TokenType::FormatSpec, literal: spec }
+    }
+
+    fn unterminated_string() -> Token {
+        Token { t_type: TokenType::Illegal, literal: "unterminated string".to_string() }
+    }
+
+    fn peek_second_char(&self) -> char {
+        self.input.get(self.curr_position + 1).copied().unwrap_or('\0')
+    }
+
+    fn next_chars_are(&self, ch: char, count: usize) -> bool {
+        (0..count).all(|offset| self.input.get(self.curr_position + offset) == Some(&ch))
+    }
+
+    /// Reads `r"..."` or `r#"..."#`, the `r` is the processed char. Nothing is escaped or interpolated,
+    /// the string ends at the first quote followed by as many `#` as the opening one.
+    fn read_raw_string(&mut self) -> Token {
+        let mut hashes = 0;
+        while self.peek_char() == '#' {
+            self.read_char();
+            hashes += 1;
+        }
+        if self.peek_char() != '"' {
+            return Token { t_type: TokenType::Illegal, literal: "expected \" to start raw string".to_string() };
+        }
+        self.read_char();
+        let mut string = String::new();
+        loop {
+            if self.peek_char() == '\0' {
+                return Token { t_type: TokenType::Illegal, literal: "unterminated raw string".to_string() };
+            }
+            self.read_char();
+            if self.processed_char == '"' && self.next_chars_are('#', hashes) {
+                for _ in 0..hashes {
+                    self.read_char();
+                }
+                return Token { t_type: TokenType::String, literal: string };
+            }
+            string.push(self.processed_char);
+        }
+    }
+
+    /// Reads a string delimited by `"""`, the first quote is the processed char. Line breaks are kept,
+    /// a line break right after the opening quotes and the indentation before the closing quotes are
+    /// dropped, and the indentation common to all lines is stripped. Escapes are processed after that,
+    /// but there is no interpolation.
+    fn read_multi_line_string(&mut self) -> Token {
+        self.read_char();
+        self.read_char();
+        let mut source = String::new();
+        while !(self.peek_char() == '"' && self.next_chars_are('"', 3)) {
+            if self.peek_char() == '\0' {
+                return Token { t_type: TokenType::Illegal, literal: "unterminated multi-line string".to_string() };
+            }
+            self.read_char();
+            source.push(self.processed_char);
+            // an escaped quote never closes the string
+            if self.processed_char == '\\' && self.peek_char() != '\0' {
+                self.read_char();
+                source.push(self.processed_char);
+            }
+        }
+        for _ in 0..3 {
+            self.read_char();
+        }
+
+        let source = strip_indentation(&source).join("\n");
+        let mut string = String::new();
+        let mut chars = source.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => string.push(chars.next().map_or('\\', unescape)),
+                other => string.push(other),
+            }
+        }
+        Token { t_type: TokenType::String, literal: string }
+    }
+
     //TODO:rename
     //TODO: extract '.' and '_'
     fn is_digit(ch: char) -> bool {
-        ch.is_numeric() || ch == '.' || ch == '_'
+        ch.is_ascii_digit() || ch == '.' || ch == '_'
     }
 
-    fn read_number(&mut self) -> String {
+    fn read_number(&mut self) -> Token {
         let mut number = String::new();
         number.push(self.processed_char);
         while Lexer::is_digit(self.peek_char()) {
             self.read_char();
             number.push(self.processed_char);
         }
-        // if number.ends_with(".") || number.ends_with("_") {
-        //TODO: verify pattern -> slice of chars vs array?
         if number.ends_with(['.', '_']) {
-            //TODO: handle panic and add unit test
-            panic!("invalid input {number}");
+            return Token { t_type: TokenType::Illegal, literal: "invalid number literal".to_string() };
         }
-        number
+        let t_type = if number.contains('.') { TokenType::Double } else { TokenType::Integer };
+        Token { t_type, literal: number }
+    }
+}
+
+fn unescape(ch: char) -> char {
+    match ch {
+        'n' => '\n',
+        't' => '\t',
+        'r' => '\r',
+        '0' => '\0',
+        other => other,
+    }
+}
+
+/// Splits the source of a multi-line string into lines without the blank first and last line
+/// and without the indentation common to all non-blank lines.
+fn strip_indentation(source: &str) -> Vec<&str> {
+    let mut lines: Vec<&str> = source.split('\n').collect();
+    if lines.len() > 1 && lines[0].trim().is_empty() {
+        lines.remove(0);
     }
+    if lines.len() > 1 && lines[lines.len() - 1].trim().is_empty() {
+        lines.pop();
+    }
+    let indentation = |line: &str| line.len() - line.trim_start_matches([' ', '\t']).len();
+    let common = lines.iter().filter(|line| !line.trim().is_empty()).map(|line| indentation(line)).min().unwrap_or(0);
+    lines.into_iter().map(|line| if line.trim().is_empty() { "" } else { &line[common..] }).collect()
 }
 
 //////////////////// Tests //////////////////////
@@ -152,7 +351,7 @@ impl Lexer {
 #[cfg(test)]
 mod test {
     use crate::lexer::Lexer;
-    use crate::token::{Token, TokenType};
+    use crate::token::{Span, Token, TokenType};
 
     #[test]
     fn test_next_token_basic_input() {
@@ -222,7 +421,7 @@ mod test {
         ];
 
         let mut lexer = Lexer::new(input);
-        for (_, expected_token) in expected.into_iter().enumerate() {
+        for expected_token in expected.into_iter() {
             let received_token = lexer.next_token();
             assert_eq!(expected_token.t_type, received_token.t_type,
                        "tests[(idx)] - token type is wrong, expected={}, got={}",
@@ -405,7 +604,7 @@ mod test {
         ];
 
         let mut lexer = Lexer::new(input);
-        for (_, expected_token) in expected.into_iter().enumerate() {
+        for expected_token in expected.into_iter() {
             let received_token = lexer.next_token();
             assert_eq!(expected_token.t_type, received_token.t_type,
                        "tests[(idx)] - token type is wrong, expected={}, got={}",
@@ -548,7 +747,7 @@ mod test {
         ];
 
         let mut lexer = Lexer::new(input);
-        for (_, expected_token) in expected.into_iter().enumerate() {
+        for expected_token in expected.into_iter() {
             let received_token = lexer.next_token();
             assert_eq!(expected_token.t_type, received_token.t_type,
                        "tests[(idx)] - token type is wrong, expected={}, got={}",
@@ -642,7 +841,129 @@ mod test {
         ];
 
         let mut lexer = Lexer::new(input);
-        for (_, expected_token) in expected.into_iter().enumerate() {
+        for expected_token in expected.into_iter() {
+            let received_token = lexer.next_token();
+            assert_eq!(expected_token.t_type, received_token.t_type,
+                       "tests[(idx)] - token type is wrong, expected={}, got={}",
+                       expected_token.t_type, received_token.t_type
+            );
+            assert_eq!(expected_token.literal, received_token.literal,
+                       "tests[(idx)] - token literal is wrong, expected={}, got={}",
+                       expected_token.literal, received_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_loops() {
+        let input: &str = r#"
+        outer: while (true) {
+            for (x in y) {
+                break outer;
+            }
+            continue;
+        }
+        "#;
+
+        let expected: Vec<Token> = vec![
+            //first line
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "outer".to_string(),
+            },
+            Token {
+                t_type: TokenType::Colon,
+                literal: ":".to_string(),
+            },
+            Token {
+                t_type: TokenType::While,
+                literal: "while".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenParenthesis,
+                literal: "(".to_string(),
+            },
+            Token {
+                t_type: TokenType::True,
+                literal: "true".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseParenthesis,
+                literal: ")".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBrace,
+                literal: "{".to_string(),
+            },
+            //second line
+            Token {
+                t_type: TokenType::For,
+                literal: "for".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenParenthesis,
+                literal: "(".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "x".to_string(),
+            },
+            Token {
+                t_type: TokenType::In,
+                literal: "in".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "y".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseParenthesis,
+                literal: ")".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBrace,
+                literal: "{".to_string(),
+            },
+            //third line
+            Token {
+                t_type: TokenType::Break,
+                literal: "break".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "outer".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            //fourth line
+            Token {
+                t_type: TokenType::CloseBrace,
+                literal: "}".to_string(),
+            },
+            //fifth line
+            Token {
+                t_type: TokenType::Continue,
+                literal: "continue".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            //sixth line
+            Token {
+                t_type: TokenType::CloseBrace,
+                literal: "}".to_string(),
+            },
+            Token {
+                t_type: TokenType::EndOfFile,
+                literal: "".to_string(),
+            },
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected.into_iter() {
             let received_token = lexer.next_token();
             assert_eq!(expected_token.t_type, received_token.t_type,
                        "tests[(idx)] - token type is wrong, expected={}, got={}",
@@ -654,4 +975,532 @@ mod test {
             );
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_next_token_arrays() {
+        let input: &str = r#"
+        [1, 2.5];
+        a[-1];
+        a[1:3];
+        "#;
+
+        let expected: Vec<Token> = vec![
+            //first line
+            Token {
+                t_type: TokenType::OpenBracket,
+                literal: "[".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::Comma,
+                literal: ",".to_string(),
+            },
+            Token {
+                t_type: TokenType::Double,
+                literal: "2.5".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBracket,
+                literal: "]".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            //second line
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "a".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBracket,
+                literal: "[".to_string(),
+            },
+            Token {
+                t_type: TokenType::Minus,
+                literal: "-".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBracket,
+                literal: "]".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            //third line
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "a".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBracket,
+                literal: "[".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::Colon,
+                literal: ":".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "3".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBracket,
+                literal: "]".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            Token {
+                t_type: TokenType::EndOfFile,
+                literal: "".to_string(),
+            },
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected.into_iter() {
+            let received_token = lexer.next_token();
+            assert_eq!(expected_token.t_type, received_token.t_type,
+                       "tests[(idx)] - token type is wrong, expected={}, got={}",
+                       expected_token.t_type, received_token.t_type
+            );
+            assert_eq!(expected_token.literal, received_token.literal,
+                       "tests[(idx)] - token literal is wrong, expected={}, got={}",
+                       expected_token.literal, received_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_hashes() {
+        let input: &str = r#"
+        {"name": "x\"y", 1: true};
+        "unterminated
+        "#;
+
+        let expected: Vec<Token> = vec![
+            //first line
+            Token {
+                t_type: TokenType::OpenBrace,
+                literal: "{".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "name".to_string(),
+            },
+            Token {
+                t_type: TokenType::Colon,
+                literal: ":".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "x\"y".to_string(),
+            },
+            Token {
+                t_type: TokenType::Comma,
+                literal: ",".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::Colon,
+                literal: ":".to_string(),
+            },
+            Token {
+                t_type: TokenType::True,
+                literal: "true".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBrace,
+                literal: "}".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            //second line
+            Token {
+                t_type: TokenType::Illegal,
+                literal: "unterminated string".to_string(),
+            },
+            Token {
+                t_type: TokenType::EndOfFile,
+                literal: "".to_string(),
+            },
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected.into_iter() {
+            let received_token = lexer.next_token();
+            assert_eq!(expected_token.t_type, received_token.t_type,
+                       "tests[(idx)] - token type is wrong, expected={}, got={}",
+                       expected_token.t_type, received_token.t_type
+            );
+            assert_eq!(expected_token.literal, received_token.literal,
+                       "tests[(idx)] - token literal is wrong, expected={}, got={}",
+                       expected_token.literal, received_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_null_handling() {
+        let input: &str = r#"
+        var a = null;
+        a?.b.c ?? 1;
+        "#;
+
+        let expected: Vec<Token> = vec![
+            //first line
+            Token {
+                t_type: TokenType::Variable,
+                literal: "var".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "a".to_string(),
+            },
+            Token {
+                t_type: TokenType::Assign,
+                literal: "=".to_string(),
+            },
+            Token {
+                t_type: TokenType::Null,
+                literal: "null".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            //second line
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "a".to_string(),
+            },
+            Token {
+                t_type: TokenType::OptionalDot,
+                literal: "?.".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "b".to_string(),
+            },
+            Token {
+                t_type: TokenType::Dot,
+                literal: ".".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "c".to_string(),
+            },
+            Token {
+                t_type: TokenType::NullCoalescing,
+                literal: "??".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            Token {
+                t_type: TokenType::EndOfFile,
+                literal: "".to_string(),
+            },
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected.into_iter() {
+            let received_token = lexer.next_token();
+            assert_eq!(expected_token.t_type, received_token.t_type,
+                       "tests[(idx)] - token type is wrong, expected={}, got={}",
+                       expected_token.t_type, received_token.t_type
+            );
+            assert_eq!(expected_token.literal, received_token.literal,
+                       "tests[(idx)] - token literal is wrong, expected={}, got={}",
+                       expected_token.literal, received_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_string_interpolation() {
+        let input: &str = r#"
+        "a ${b + {"c": 1}["c"]} d ${e:>4} f"
+        "${"in ${y}"}!"
+        a[1:2] "\${not interpolated}"
+        "#;
+
+        let expected: Vec<Token> = vec![
+            //first line
+            Token {
+                t_type: TokenType::StringStart,
+                literal: "a ".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "b".to_string(),
+            },
+            Token {
+                t_type: TokenType::Plus,
+                literal: "+".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBrace,
+                literal: "{".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "c".to_string(),
+            },
+            Token {
+                t_type: TokenType::Colon,
+                literal: ":".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBrace,
+                literal: "}".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBracket,
+                literal: "[".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "c".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBracket,
+                literal: "]".to_string(),
+            },
+            Token {
+                t_type: TokenType::StringMiddle,
+                literal: " d ".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "e".to_string(),
+            },
+            Token {
+                t_type: TokenType::FormatSpec,
+                literal: ">4".to_string(),
+            },
+            Token {
+                t_type: TokenType::StringEnd,
+                literal: " f".to_string(),
+            },
+            //second line
+            Token {
+                t_type: TokenType::StringStart,
+                literal: "".to_string(),
+            },
+            Token {
+                t_type: TokenType::StringStart,
+                literal: "in ".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "y".to_string(),
+            },
+            Token {
+                t_type: TokenType::StringEnd,
+                literal: "".to_string(),
+            },
+            Token {
+                t_type: TokenType::StringEnd,
+                literal: "!".to_string(),
+            },
+            //third line
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "a".to_string(),
+            },
+            Token {
+                t_type: TokenType::OpenBracket,
+                literal: "[".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "1".to_string(),
+            },
+            Token {
+                t_type: TokenType::Colon,
+                literal: ":".to_string(),
+            },
+            Token {
+                t_type: TokenType::Integer,
+                literal: "2".to_string(),
+            },
+            Token {
+                t_type: TokenType::CloseBracket,
+                literal: "]".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "${not interpolated}".to_string(),
+            },
+            Token {
+                t_type: TokenType::EndOfFile,
+                literal: "".to_string(),
+            },
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected.into_iter() {
+            let received_token = lexer.next_token();
+            assert_eq!(expected_token.t_type, received_token.t_type,
+                       "tests[(idx)] - token type is wrong, expected={}, got={}",
+                       expected_token.t_type, received_token.t_type
+            );
+            assert_eq!(expected_token.literal, received_token.literal,
+                       "tests[(idx)] - token literal is wrong, expected={}, got={}",
+                       expected_token.literal, received_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_raw_strings() {
+        let input: &str = r###"
+        r"C:\temp\new"
+        r#"say "hi" ${x}"#
+        r##""#"##
+        r = "a\tb";
+        "###;
+
+        let expected: Vec<Token> = vec![
+            Token {
+                t_type: TokenType::String,
+                literal: "C:\\temp\\new".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "say \"hi\" ${x}".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "\"#".to_string(),
+            },
+            Token {
+                t_type: TokenType::Identifier,
+                literal: "r".to_string(),
+            },
+            Token {
+                t_type: TokenType::Assign,
+                literal: "=".to_string(),
+            },
+            Token {
+                t_type: TokenType::String,
+                literal: "a\tb".to_string(),
+            },
+            Token {
+                t_type: TokenType::Semicolon,
+                literal: ";".to_string(),
+            },
+            Token {
+                t_type: TokenType::EndOfFile,
+                literal: "".to_string(),
+            },
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for expected_token in expected.into_iter() {
+            let received_token = lexer.next_token();
+            assert_eq!(expected_token.t_type, received_token.t_type,
+                       "tests[(idx)] - token type is wrong, expected={}, got={}",
+                       expected_token.t_type, received_token.t_type
+            );
+            assert_eq!(expected_token.literal, received_token.literal,
+                       "tests[(idx)] - token literal is wrong, expected={}, got={}",
+                       expected_token.literal, received_token.literal
+            );
+        }
+    }
+
+    #[test]
+    fn test_next_token_multi_line_strings() {
+        let tests: Vec<(&str, &str)> = vec![
+            ("\"\"\"one line\"\"\"", "one line"),
+            ("\"\"\"\"\"\"", ""),
+            ("\"\"\"\n    SELECT *\n      FROM t\n\n    WHERE x\n    \"\"\"", "SELECT *\n  FROM t\n\nWHERE x"),
+            ("\"\"\"\n\tindented\n\t\ttabs\n\t\"\"\"", "indented\n\ttabs"),
+            ("\"\"\"first\n    second\"\"\"", "first\n    second"),
+            ("\"\"\"\n  a \\\"\"\" b\\n ${c}\n  \"\"\"", "a \"\"\" b\n ${c}"),
+        ];
+
+        for (input, expected) in tests {
+            let token = Lexer::new(input).next_token();
+            assert_eq!(TokenType::String, token.t_type, "wrong token type for {input}");
+            assert_eq!(expected, token.literal, "wrong literal for {input}");
+        }
+    }
+
+    #[test]
+    fn test_illegal_literals() {
+        let tests: Vec<(&str, &str)> = vec![
+            ("\"abc", "unterminated string"),
+            ("r\"abc", "unterminated raw string"),
+            ("r#\"abc\"", "unterminated raw string"),
+            ("r#abc", "expected \" to start raw string"),
+            ("\"\"\"abc\"\"", "unterminated multi-line string"),
+            ("1.", "invalid number literal"),
+            ("5.x", "invalid number literal"),
+            ("1__", "invalid number literal"),
+            ("٣", "٣"),
+        ];
+
+        for (input, expected) in tests {
+            let token = Lexer::new(input).next_token();
+            assert_eq!(TokenType::Illegal, token.t_type, "wrong token type for {input}");
+            assert_eq!(expected, token.literal, "wrong literal for {input}");
+        }
+    }
+
+    #[test]
+    fn test_token_spans() {
+        let input = "var x = 1;\n  r\"raw\" + \"\"\"\n  multi\n  \"\"\" +\n\"${x}\"";
+        let expected: Vec<(TokenType, usize, usize)> = vec![
+            (TokenType::Variable, 1, 1),
+            (TokenType::Identifier, 1, 5),
+            (TokenType::Assign, 1, 7),
+            (TokenType::Integer, 1, 9),
+            (TokenType::Semicolon, 1, 10),
+            (TokenType::String, 2, 3),
+            (TokenType::Plus, 2, 10),
+            (TokenType::String, 2, 12),
+            (TokenType::Plus, 4, 7),
+            (TokenType::StringStart, 5, 1),
+            (TokenType::Identifier, 5, 4),
+            (TokenType::StringEnd, 5, 5),
+            (TokenType::EndOfFile, 5, 7),
+        ];
+
+        let mut lexer = Lexer::new(input);
+        for (t_type, line, column) in expected {
+            let token = lexer.next_token();
+            assert_eq!(t_type, token.t_type);
+            assert_eq!(Span { line, column }, lexer.span(), "wrong span for {t_type}");
+        }
+    }
+}
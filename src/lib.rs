@@ -0,0 +1,25 @@
+//! A scripting language interpreter. Hosts embed it through `Interpreter`, which runs source code and
+//! exchanges values with it, see `IntoValue` and `FromValue` for the conversions.
+
+pub mod token;
+pub mod lexer;
+pub mod ast;
+pub mod parser;
+pub mod bigint;
+pub mod object;
+pub mod environment;
+pub mod gc;
+pub mod evaluator;
+pub mod resolver;
+pub mod module;
+pub mod prelude;
+pub mod builtins;
+pub mod value;
+pub mod native;
+pub mod interpreter;
+
+pub use crate::evaluator::{Config, Limits};
+pub use crate::interpreter::{Error, Interpreter};
+pub use crate::native::{IntoResult, Native, NativeFunction};
+pub use crate::object::{ErrorKind, Object, RuntimeError};
+pub use crate::value::{FromValue, IntoValue};
@@ -1,11 +1,49 @@
-use crate::repl::start;
+use std::time::Duration;
+use interpreter_in_rust::evaluator::{Config, IntegerOverflow, Truthiness};
+use crate::repl::{run_file, start};
 
-pub mod token;
-pub mod lexer;
-pub mod repl;
+mod repl;
+
+const USAGE: &str = "usage: interpreter-in-rust [--strict | --loose] [--wrapping] [--max-call-depth=N] [--gc-threshold=N]\n       [--max-steps=N] [--max-heap=BYTES] [--timeout=MS] [--no-io] [--no-prelude] [script]";
+
+/// The value of a `--name=N` option.
+fn number(option: &str) -> usize {
+    let (_, value) = option.split_once('=').expect("options with a value contain =");
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("invalid number in {option}\n{USAGE}");
+        std::process::exit(2);
+    })
+}
 
 fn main() {
+    let mut config = Config::default();
+    let mut path = None;
+    for argument in std::env::args().skip(1) {
+        match argument.as_str() {
+            "--strict" => config.truthiness = Truthiness::Strict,
+            "--loose" => config.truthiness = Truthiness::Loose,
+            "--wrapping" => config.overflow = IntegerOverflow::Wrapping,
+            option if option.starts_with("--max-call-depth=") => config.max_call_depth = number(option),
+            option if option.starts_with("--gc-threshold=") => config.gc_threshold = number(option),
+            option if option.starts_with("--max-steps=") => config.limits.max_steps = Some(number(option) as u64),
+            option if option.starts_with("--max-heap=") => config.limits.max_heap_bytes = Some(number(option)),
+            option if option.starts_with("--timeout=") => {
+                config.limits.timeout = Some(Duration::from_millis(number(option) as u64))
+            }
+            "--no-io" => config.limits.disable_io = true,
+            "--no-prelude" => config.prelude = false,
+            option if option.starts_with("--") => {
+                eprintln!("unknown option {option}\n{USAGE}");
+                std::process::exit(2);
+            }
+            _ => path = Some(argument),
+        }
+    }
+
+    if let Some(path) = path {
+        std::process::exit(run_file(&path, config));
+    }
     println!("Welcome to the test repl.");
     println!("Enter your code here.");
-    start(std::io::stdin(), std::io::stdout()); //TODO: use std::io::{...}?
+    start(std::io::stdin(), std::io::stdout(), config); //TODO: use std::io::{...}?
 }
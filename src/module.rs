@@ -0,0 +1,217 @@
+//! Modules are script files run by `import`. A path is relative to the directory of the importing file,
+//! or to the current directory outside of files, and gets the `.mk` extension when it has none. Each
+//! module runs once, the first time it is imported, and every import gets the same hash of its exports.
+//!
+//! A module has its own top level. Besides the builtins and its imports it sees the standard library and
+//! the globals defined by the host with `Interpreter::set_global` and `Interpreter::register`, but not the
+//! variables of its importer.
+
+use std::path::{Path, PathBuf};
+use crate::ast::Program;
+use crate::lexer::Lexer;
+use crate::object::{ErrorKind, RuntimeError};
+use crate::parser::Parser;
+use crate::resolver::{Resolver, Severity};
+
+fn import_error(message: String) -> RuntimeError {
+    RuntimeError::new(ErrorKind::ImportError, message)
+}
+
+/// The file `path` refers to when imported from `importer`, made absolute so that every way of naming
+/// a module finds it in the cache.
+pub(crate) fn locate(path: &str, importer: Option<&PathBuf>) -> Result<PathBuf, RuntimeError> {
+    let base = importer.and_then(|importer| importer.parent()).unwrap_or(Path::new("."));
+    let mut file = base.join(path);
+    if file.extension().is_none() {
+        file.set_extension("mk");
+    }
+    file.canonicalize().map_err(|e| import_error(format!("cannot find module {path:?}: {e}")))
+}
+
+/// Shows `file` relative to the directory of `root`, the first file of the run, when it is inside it.
+pub(crate) fn display(file: &Path, root: Option<&PathBuf>) -> String {
+    let directory = root.and_then(|root| root.parent());
+    let relative = directory.and_then(|directory| file.strip_prefix(directory).ok());
+    relative.unwrap_or(file).display().to_string()
+}
+
+/// Reads and checks the module in `file`, which may use the `globals` besides the builtins. A module with
+/// errors does not run at all, the errors of its source have their location in the module.
+pub(crate) fn load(file: &Path, globals: &[String]) -> Result<Program, RuntimeError> {
+    let source = std::fs::read_to_string(file).map_err(|e| import_error(format!("cannot read module: {e}")))?;
+    parse(&source, globals)
+}
+
+/// Parses and checks the source of a module, see `load`.
+pub(crate) fn parse(source: &str, globals: &[String]) -> Result<Program, RuntimeError> {
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+    if let Some(error) = parser.errors().first() {
+        return Err(import_error(error.clone()));
+    }
+    let mut resolver = Resolver::new();
+    for name in globals {
+        resolver.declare_global(name);
+    }
+    let resolved = resolver.resolve(&program);
+    if let Some(error) = resolved.diagnostics.iter().find(|diagnostic| diagnostic.severity == Severity::Error) {
+        let mut e = import_error(error.message.clone());
+        e.span = Some(error.span);
+        return Err(e);
+    }
+    Ok(program)
+}
+
+/// Describes the imports from the first import of `file` in `loading` back to `file`.
+pub(crate) fn cycle_error(loading: &[PathBuf], file: &Path) -> RuntimeError {
+    let root = loading.first();
+    let start = loading.iter().position(|loading| loading == file).unwrap_or(0);
+    let mut chain: Vec<String> = loading[start..].iter().map(|loading| display(loading, root)).collect();
+    chain.push(display(file, root));
+    import_error(format!("import cycle: {}", chain.join(" -> ")))
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+    use crate::evaluator::{Config, Limits};
+    use crate::interpreter::{Error, Interpreter};
+    use crate::object::ErrorKind;
+    use crate::resolver::Severity;
+
+    type Files<'a> = &'a [(&'a str, &'a str)];
+
+    /// Writes `files` to a fresh directory, returns the path of its `main.mk`.
+    fn project(name: &str, files: Files) -> PathBuf {
+        let directory = std::env::temp_dir().join(format!("interpreter-modules-{}-{name}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        for (path, source) in files {
+            let path = directory.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, source).unwrap();
+        }
+        directory.join("main.mk")
+    }
+
+    fn run(name: &str, files: Files) -> Result<String, Error> {
+        Interpreter::new().eval_file(project(name, files)).map(|value| value.to_string())
+    }
+
+    fn runtime_error(result: Result<String, Error>) -> (ErrorKind, String) {
+        match result {
+            Err(Error::Runtime(e)) => (e.kind, e.message),
+            other => panic!("expected a runtime error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_imports() {
+        let files = [
+            ("main.mk", r#"import "lib/math"; import "lib/shapes.mk"; [math.add(1, 2), shapes.area(2), math.pi]"#),
+            ("lib/math.mk", "import \"constants\"\nexport var add = func(a, b) { a + b }\nexport var pi = constants.pi"),
+            ("lib/constants.mk", "export var pi = 3.14\nvar hidden = 1"),
+            ("lib/shapes.mk", r#"import "../lib/math"; export var area = func(r) { math.pi * r * r }"#),
+        ];
+        assert_eq!("[3, 12.56, 3.14]", run("imports", &files).unwrap());
+
+        let files = [("main.mk", r#"import "constants"; constants.hidden"#), ("constants.mk", "export var pi = 3\nvar hidden = 1")];
+        assert_eq!((ErrorKind::KeyError, r#"key not found: "hidden""#.to_string()), runtime_error(run("hidden", &files)));
+    }
+
+    #[test]
+    fn test_modules_run_once() {
+        let files = [
+            ("main.mk", r#"import "a"; import "b"; import "counter"; [a.count, b.count, counter.runs]"#),
+            ("a.mk", r#"import "counter"; export var count = len(counter.runs)"#),
+            ("b.mk", r#"import "./counter.mk"; export var count = len(counter.runs)"#),
+            ("counter.mk", "export var runs = []\npush(runs, 1)"),
+        ];
+        assert_eq!("[1, 1, [1]]", run("once", &files).unwrap());
+    }
+
+    #[test]
+    fn test_globals() {
+        let files = [
+            ("main.mk", r#"var secret = 1; import "lib"; lib.scaled(2)"#),
+            ("lib.mk", "export var scaled = func(x) { multiply(x, factor) }"),
+        ];
+        let mut interpreter = Interpreter::new();
+        interpreter.set_global("factor", 10);
+        interpreter.register("multiply", |a: i64, b: i64| a * b);
+        assert_eq!("20", interpreter.eval_file(project("host", &files)).unwrap().to_string());
+
+        let files = [
+            ("main.mk", r#"var pad_left = 1; import "util"; util.code("7")"#),
+            ("util.mk", r#"export var code = func(s) { pad_left(s, 3, "0") }"#),
+        ];
+        assert_eq!("007", run("prelude", &files).unwrap());
+
+        // the variables of the importer stay out of the module
+        let files = [("main.mk", r#"var secret = 1; import "lib"; 1"#), ("lib.mk", "secret")];
+        assert_eq!((ErrorKind::ImportError, "identifier not found: secret".to_string()), runtime_error(run("private", &files)));
+    }
+
+    #[test]
+    fn test_import_cycles() {
+        let files = [
+            ("main.mk", r#"import "a"; 1"#),
+            ("a.mk", r#"import "b"; export var a = 1"#),
+            ("b.mk", r#"import "c"; export var b = 1"#),
+            ("c.mk", r#"import "a"; export var c = 1"#),
+        ];
+        let expected = "import cycle: a.mk -> b.mk -> c.mk -> a.mk";
+        assert_eq!((ErrorKind::ImportError, expected.to_string()), runtime_error(run("cycle", &files)));
+
+        let files = [("main.mk", r#"import "main"; 1"#)];
+        let expected = "import cycle: main.mk -> main.mk";
+        assert_eq!((ErrorKind::ImportError, expected.to_string()), runtime_error(run("self", &files)));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let tests: Vec<(&str, Files, &str)> = vec![
+            ("syntax", &[("main.mk", r#"import "bad""#), ("bad.mk", "var = 1")], "ImportError: expected next token to be Identifier, got = instead\n    in <module bad.mk> called at 1:8"),
+            ("resolve", &[("main.mk", r#"import "bad""#), ("bad.mk", "\nmissing")], "ImportError: identifier not found: missing at bad.mk:2:1\n    in <module bad.mk> called at 1:8"),
+            ("catch", &[("main.mk", r#"try { import "nope" } catch (e) { throw e.kind }"#)], "Error: ImportError at 1:35"),
+        ];
+        for (name, files, expected) in tests {
+            match Interpreter::new().eval_file(project(name, files)) {
+                Err(Error::Runtime(e)) => assert_eq!(expected, e.traceback(), "wrong error for {name}"),
+                other => panic!("expected a runtime error for {name}, got {other:?}"),
+            }
+        }
+
+        let (kind, message) = runtime_error(run("missing", &[("main.mk", r#"import "nope""#)]));
+        assert_eq!(ErrorKind::ImportError, kind);
+        assert!(message.starts_with(r#"cannot find module "nope": "#), "{message}");
+
+        let files = [("main.mk", "import \"failing\"\n"), ("failing.mk", "var f = func() { 1 / 0 }\nf()")];
+        match Interpreter::new().eval_file(project("traceback", &files)) {
+            Err(Error::Runtime(e)) => assert_eq!(
+                "ArithmeticError: division by zero at failing.mk:1:20\n    in f called at failing.mk:2:2\n    in <module failing.mk> called at 1:8",
+                e.traceback(),
+            ),
+            other => panic!("expected a runtime error, got {other:?}"),
+        }
+
+        let limits = Limits { disable_io: true, ..Limits::default() };
+        let mut interpreter = Interpreter::with_config(Config { limits, ..Config::default() });
+        let files = [("main.mk", r#"try { import "lib" } catch (e) { 1 }"#), ("lib.mk", "")];
+        match interpreter.eval_file(project("sandbox", &files)) {
+            Err(Error::Runtime(e)) => assert_eq!((ErrorKind::PermissionError, "import: I/O is disabled"), (e.kind, e.message.as_str())),
+            other => panic!("expected a runtime error, got {other:?}"),
+        }
+
+        let files = [("main.mk", r#"var f = func() { import "lib" }; export var x = 1; if (true) { export var y = 2 }"#)];
+        match run("nested", &files) {
+            Err(Error::Resolve(diagnostics)) => {
+                let errors = diagnostics.iter().filter(|diagnostic| diagnostic.severity == Severity::Error);
+                let messages: Vec<&str> = errors.map(|diagnostic| diagnostic.message.as_str()).collect();
+                assert_eq!(vec!["import inside a function", "export outside of the top level"], messages);
+            }
+            other => panic!("expected resolve errors, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,272 @@
+//! Functions written in Rust by the host, callable from scripts like builtins. Their arguments and result
+//! are converted with `FromValue` and `IntoValue`, see `Interpreter::register`.
+
+use std::fmt::{Debug, Formatter};
+use crate::object::{ErrorKind, Object, RuntimeError};
+use crate::value::{FromValue, IntoValue};
+
+/// What a native function returns: a value, or a `Result` whose error becomes a script error.
+pub trait IntoResult {
+    fn into_result(self) -> Result<Object, RuntimeError>;
+}
+
+impl<T: IntoValue> IntoResult for T {
+    fn into_result(self) -> Result<Object, RuntimeError> {
+        Ok(self.into_value())
+    }
+}
+
+/// Errors convert with `Into<RuntimeError>`, which strings and I/O errors already do.
+impl<T: IntoValue, E: Into<RuntimeError>> IntoResult for Result<T, E> {
+    fn into_result(self) -> Result<Object, RuntimeError> {
+        self.map(T::into_value).map_err(E::into)
+    }
+}
+
+/// A Rust closure taking the arguments `Args` as a tuple of `FromValue` types.
+pub trait NativeFunction<Args> {
+    const ARITY: usize;
+
+    /// Converts the arguments and calls the closure, `arguments` has exactly `ARITY` values.
+    fn call(&self, name: &str, arguments: Vec<Object>) -> Result<Object, RuntimeError>;
+}
+
+/// Converts the argument at `position`, counted from 1, blaming the function for a wrong type.
+fn argument<T: FromValue>(name: &str, position: usize, value: Object) -> Result<T, RuntimeError> {
+    T::from_value(value).map_err(|e| RuntimeError::new(e.kind, format!("{name}: argument {position}: {}", e.message)))
+}
+
+macro_rules! native_functions {
+    ($($arity:literal: ($($argument:ident),*);)*) => {$(
+        #[allow(non_snake_case)]
+        impl<F, R, $($argument),*> NativeFunction<($($argument,)*)> for F
+        where
+            F: Fn($($argument),*) -> R,
+            R: IntoResult,
+            $($argument: FromValue,)*
+        {
+            const ARITY: usize = $arity;
+
+            #[allow(unused_variables, unused_mut)]
+            fn call(&self, name: &str, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+                let mut arguments = arguments.into_iter();
+                let mut position = 0;
+                $(
+                    position += 1;
+                    let $argument = argument(name, position, arguments.next().expect("the arity is checked"))?;
+                )*
+                self($($argument),*).into_result()
+            }
+        }
+    )*};
+}
+
+native_functions! {
+    0: ();
+    1: (A);
+    2: (A, B);
+    3: (A, B, C);
+    4: (A, B, C, D);
+    5: (A, B, C, D, E);
+    6: (A, B, C, D, E, G);
+}
+
+type Call = Box<dyn Fn(&str, Vec<Object>) -> Result<Object, RuntimeError>>;
+
+/// A native function as a script value.
+pub struct Native {
+    pub name: String,
+    pub arity: usize,
+    function: Call,
+}
+
+impl Native {
+    pub fn new<Args, F: NativeFunction<Args> + 'static>(name: &str, function: F) -> Native {
+        Native {
+            name: name.to_string(),
+            arity: F::ARITY,
+            function: Box::new(move |name, arguments| function.call(name, arguments)),
+        }
+    }
+
+    /// Checks the number of arguments and calls the function.
+    pub fn call(&self, arguments: Vec<Object>) -> Result<Object, RuntimeError> {
+        if arguments.len() != self.arity {
+            return Err(RuntimeError::new(ErrorKind::ArgumentError, format!(
+                "{}: wrong number of arguments: expected {}, got {}", self.name, self.arity, arguments.len()
+            )));
+        }
+        (self.function)(&self.name, arguments)
+    }
+}
+
+impl Debug for Native {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Native").field("name", &self.name).field("arity", &self.arity).finish()
+    }
+}
+
+/// Registers the methods of a Rust value as a hash of native functions, so scripts call them with
+/// `name.method(...)`. The value is moved into an `Rc<RefCell<_>>` shared by the methods, which the
+/// macro returns so the host can still use it. Methods taking `&mut self` are allowed.
+///
+/// ```
+/// use interpreter_in_rust::{register_methods, Interpreter};
+///
+/// struct Counter { total: i64 }
+///
+/// impl Counter {
+///     fn add(&mut self, amount: i64) -> i64 { self.total += amount; self.total }
+///     fn total(&self) -> i64 { self.total }
+/// }
+///
+/// let mut interpreter = Interpreter::new();
+/// let counter = register_methods!(interpreter, "counter", Counter { total: 0 }, {
+///     fn add(amount: i64);
+///     fn total();
+/// });
+/// interpreter.eval("counter.add(2); counter.add(3)").unwrap();
+/// assert_eq!(5, counter.borrow().total());
+/// ```
+#[macro_export]
+macro_rules! register_methods {
+    ($interpreter:expr, $name:literal, $value:expr, { $(fn $method:ident($($argument:ident: $type:ty),* $(,)?);)* }) => {{
+        let value = ::std::rc::Rc::new(::std::cell::RefCell::new($value));
+        let mut methods = $crate::object::Hash::new();
+        $(
+            let target = value.clone();
+            let function = $crate::native::Native::new(
+                concat!($name, ".", stringify!($method)),
+                move |$($argument: $type),*| target.borrow_mut().$method($($argument),*),
+            );
+            methods.insert(
+                $crate::object::Object::String(stringify!($method).to_string()),
+                $crate::object::Object::Native(::std::rc::Rc::new(function)),
+            ).expect("strings are valid hash keys");
+        )*
+        $interpreter.set_global($name, $crate::object::Object::new_hash(methods));
+        value
+    }};
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use crate::interpreter::{Error, Interpreter};
+    use crate::object::{ErrorKind, Object};
+
+    fn eval(interpreter: &mut Interpreter, input: &str) -> Result<String, (ErrorKind, String)> {
+        match interpreter.eval(input) {
+            Ok(value) => Ok(value.to_string()),
+            Err(Error::Runtime(e)) => Err((e.kind, e.message)),
+            Err(e) => panic!("unexpected error for {input}: {e}"),
+        }
+    }
+
+    #[test]
+    fn test_register() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register("answer", || 42);
+        interpreter.register("add", |a: i64, b: i64| a + b);
+        interpreter.register("greet", |name: String, excited: bool| format!("hi {name}{}", if excited { "!" } else { "" }));
+        interpreter.register("average", |values: Vec<f64>| values.iter().sum::<f64>() / values.len() as f64);
+        interpreter.register("first", |values: Vec<Object>| values.into_iter().next());
+        let calls = Rc::new(Cell::new(0));
+        let counted = calls.clone();
+        interpreter.register("tick", move || counted.set(counted.get() + 1));
+
+        let tests = vec![
+            ("answer()", "42"),
+            ("add(2, add(3, 4))", "9"),
+            (r#"greet("ann", true)"#, "hi ann!"),
+            ("average([1, 2.5, 4.5])", "2.6666666666666665"),
+            ("first([])", "null"),
+            ("first([[1]])", "[1]"),
+            ("var f = add; f(1, 1)", "2"),
+            ("add", "builtin function add"),
+            ("tick(); tick()", "null"),
+        ];
+        for (input, expected) in tests {
+            assert_eq!(Ok(expected.to_string()), eval(&mut interpreter, input), "wrong value for {input}");
+        }
+        assert_eq!(2, calls.get());
+    }
+
+    #[test]
+    fn test_deep_recursion_stays_on_the_host_thread() {
+        let mut interpreter = Interpreter::new();
+        let host = std::thread::current().id();
+        interpreter.register("on_host", move || std::thread::current().id() == host);
+
+        let input = "var f = func(n) { if (n == 0) { on_host() } else { f(n - 1) } }; f(100000)";
+        assert_eq!(Ok("true".to_string()), eval(&mut interpreter, input));
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let mut interpreter = Interpreter::new();
+        interpreter.register("add", |a: i64, b: i64| a + b);
+        interpreter.register("parse", |text: String| text.parse::<i64>().map_err(|e| format!("parse: {e}")));
+        interpreter.register("read", |path: String| std::fs::read_to_string(path));
+
+        let tests = vec![
+            ("add(1)", ErrorKind::ArgumentError, "add: wrong number of arguments: expected 2, got 1"),
+            (r#"add(1, "2")"#, ErrorKind::TypeError, "add: argument 2: expected INTEGER, got STRING"),
+            ("add(1, 2.0)", ErrorKind::TypeError, "add: argument 2: expected INTEGER, got DOUBLE"),
+            (r#"parse("x")"#, ErrorKind::Error, "parse: invalid digit found in string"),
+            (r#"read("/nonexistent/file")"#, ErrorKind::IoError, "No such file or directory (os error 2)"),
+        ];
+        for (input, kind, message) in tests {
+            assert_eq!(Err((kind, message.to_string())), eval(&mut interpreter, input), "wrong error for {input}");
+        }
+        assert_eq!(Ok("7".to_string()), eval(&mut interpreter, r#"parse("7")"#));
+        assert_eq!(
+            Ok("ArgumentError".to_string()),
+            eval(&mut interpreter, "try { add() } catch (e) { e.kind }"),
+        );
+    }
+
+    struct Account {
+        balance: i64,
+    }
+
+    impl Account {
+        fn deposit(&mut self, amount: i64) -> Result<i64, String> {
+            if amount <= 0 {
+                return Err(format!("invalid amount: {amount}"));
+            }
+            self.balance += amount;
+            Ok(self.balance)
+        }
+
+        fn balance(&self) -> i64 {
+            self.balance
+        }
+    }
+
+    #[test]
+    fn test_register_methods() {
+        let mut interpreter = Interpreter::new();
+        let account = register_methods!(interpreter, "account", Account { balance: 10 }, {
+            fn deposit(amount: i64);
+            fn balance();
+        });
+
+        assert_eq!(Ok("15".to_string()), eval(&mut interpreter, "account.deposit(5)"));
+        assert_eq!(Ok("15".to_string()), eval(&mut interpreter, "account.balance()"));
+        assert_eq!(
+            Err((ErrorKind::Error, "invalid amount: -1".to_string())),
+            eval(&mut interpreter, "account.deposit(-1)"),
+        );
+        assert_eq!(
+            Err((ErrorKind::ArgumentError, "account.balance: wrong number of arguments: expected 0, got 1".to_string())),
+            eval(&mut interpreter, "account.balance(1)"),
+        );
+        assert_eq!(15, account.borrow().balance());
+        account.borrow_mut().balance = 0;
+        assert_eq!(Ok("0".to_string()), eval(&mut interpreter, "account.balance()"));
+    }
+}
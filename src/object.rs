@@ -0,0 +1,398 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::{Debug, Display, Formatter, Result};
+use std::rc::Rc;
+use crate::ast::{Alignment, BlockStatement, FormatSpec};
+use crate::bigint::BigInt;
+use crate::environment::Env;
+use crate::evaluator::Evaluator;
+use crate::native::Native;
+use crate::token::Span;
+
+#[derive(Debug, Clone)]
+pub enum Object {
+    Integer(i64),
+    /// Only holds values outside of the i64 range, smaller ones are always `Integer`.
+    BigInteger(BigInt),
+    Double(f64),
+    Boolean(bool),
+    String(String),
+    Null,
+    Function(Rc<Function>),
+    Builtin(Builtin),
+    /// A function of the host, see `Interpreter::register`.
+    Native(Rc<Native>),
+    Array(Rc<RefCell<Vec<Object>>>),
+    Hash(Rc<RefCell<Hash>>),
+    /// A runtime error caught by `catch`.
+    Error(Rc<RuntimeError>),
+    Ok(Box<Object>),
+    Err(Box<Object>),
+}
+
+#[derive(PartialEq, Eq, Hash, Debug, Clone)]
+pub enum HashKey {
+    Integer(i64),
+    BigInteger(BigInt),
+    String(String),
+    Boolean(bool),
+}
+
+/// Hash map remembering the insertion order of its keys, so iterating and printing it is deterministic.
+#[derive(Debug, Default)]
+pub struct Hash {
+    entries: Vec<(Object, Object)>,
+    positions: HashMap<HashKey, usize>,
+}
+
+impl Hash {
+    pub fn new() -> Hash {
+        Default::default()
+    }
+
+    /// Inserts or replaces the value of `key`, a replaced key keeps its original position.
+    pub fn insert(&mut self, key: Object, value: Object) -> std::result::Result<(), RuntimeError> {
+        let hash_key = key.hash_key()?;
+        match self.positions.get(&hash_key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(hash_key, self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, key: &Object) -> std::result::Result<Option<Object>, RuntimeError> {
+        let hash_key = key.hash_key()?;
+        Ok(self.positions.get(&hash_key).map(|&position| self.entries[position].1.clone()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn keys(&self) -> Vec<Object> {
+        self.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    pub fn entries(&self) -> &[(Object, Object)] {
+        &self.entries
+    }
+
+    /// Removes every entry, returning them.
+    pub fn clear(&mut self) -> Vec<(Object, Object)> {
+        self.positions.clear();
+        std::mem::take(&mut self.entries)
+    }
+}
+
+pub struct Function {
+    pub name: Option<String>,
+    pub parameters: Vec<String>,
+    pub body: BlockStatement,
+    /// The scope the function was created in. Captured by reference: the variables stay alive as long as
+    /// the function does, and assignments made by the function or by anyone sharing the scope are seen by all.
+    pub env: Env,
+    /// The module or prelude file the function was created in, `None` for the main program.
+    pub file: Option<Rc<str>>,
+}
+
+pub type BuiltinFunction = fn(&mut Evaluator, Vec<Object>) -> std::result::Result<Object, RuntimeError>;
+
+#[derive(Debug, Clone)]
+pub struct Builtin {
+    pub name: &'static str,
+    pub function: BuiltinFunction,
+}
+
+impl Object {
+    pub fn new_array(elements: Vec<Object>) -> Object {
+        Object::Array(Rc::new(RefCell::new(elements)))
+    }
+
+    /// Keeps the invariant that big integers are only used beyond the i64 range.
+    pub fn from_big_integer(value: BigInt) -> Object {
+        match value.to_i64() {
+            Some(value) => Object::Integer(value),
+            None => Object::BigInteger(value),
+        }
+    }
+
+    pub fn new_hash(hash: Hash) -> Object {
+        Object::Hash(Rc::new(RefCell::new(hash)))
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) | Object::BigInteger(_) => "INTEGER",
+            Object::Double(_) => "DOUBLE",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Null => "NULL",
+            Object::Function(_) => "FUNCTION",
+            Object::Builtin(_) | Object::Native(_) => "BUILTIN",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Error(_) => "ERROR",
+            Object::Ok(_) | Object::Err(_) => "RESULT",
+        }
+    }
+
+    pub fn hash_key(&self) -> std::result::Result<HashKey, RuntimeError> {
+        match self {
+            Object::Integer(value) => Ok(HashKey::Integer(*value)),
+            Object::BigInteger(value) => Ok(HashKey::BigInteger(value.clone())),
+            Object::String(value) => Ok(HashKey::String(value.clone())),
+            Object::Boolean(value) => Ok(HashKey::Boolean(*value)),
+            other => {
+                Err(RuntimeError::new(ErrorKind::TypeError, format!("unusable as hash key: {}", other.type_name())))
+            }
+        }
+    }
+
+    /// Formats the value for an interpolation. Precision sets the decimals of numbers and truncates
+    /// anything else, numbers are aligned right by default and zero padding only applies to them.
+    pub fn format(&self, spec: &FormatSpec) -> String {
+        let text = match (self, spec.precision) {
+            (Object::Double(value), Some(precision)) => format!("{value:.precision$}"),
+            (Object::Integer(_) | Object::BigInteger(_), Some(0)) => self.to_string(),
+            (Object::Integer(_) | Object::BigInteger(_), Some(precision)) => format!("{self}.{}", "0".repeat(precision)),
+            (other, Some(precision)) => other.to_string().chars().take(precision).collect(),
+            (other, None) => other.to_string(),
+        };
+        let numeric = matches!(self, Object::Integer(_) | Object::BigInteger(_) | Object::Double(_));
+        let padding = spec.width.unwrap_or(0).saturating_sub(text.chars().count());
+        if padding == 0 {
+            return text;
+        }
+        // zeros would turn inf and NaN into nonsense, those are padded with spaces
+        if spec.zero_pad && numeric && !matches!(self, Object::Double(value) if !value.is_finite()) {
+            let (sign, digits) = match text.strip_prefix('-') {
+                Some(digits) => ("-", digits),
+                None => ("", text.as_str()),
+            };
+            return format!("{sign}{}{digits}", "0".repeat(padding));
+        }
+        let alignment = spec.alignment.unwrap_or(if numeric { Alignment::Right } else { Alignment::Left });
+        let (left, right) = match alignment {
+            Alignment::Left => (0, padding),
+            Alignment::Center => (padding / 2, padding - padding / 2),
+            Alignment::Right => (padding, 0),
+        };
+        format!("{}{text}{}", " ".repeat(left), " ".repeat(right))
+    }
+
+    /// Like `to_string`, but quotes strings. Used for values nested in collections.
+    pub fn inspect(&self) -> String {
+        match self {
+            Object::String(value) => format!("{value:?}"),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl Display for Object {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        match self {
+            Object::Integer(value) => write!(f, "{value}"),
+            Object::BigInteger(value) => write!(f, "{value}"),
+            Object::Double(value) => write!(f, "{value:?}"),
+            Object::Boolean(value) => write!(f, "{value}"),
+            Object::String(value) => write!(f, "{value}"),
+            Object::Null => write!(f, "null"),
+            Object::Function(function) => write!(f, "func({}) {}", function.parameters.join(", "), function.body),
+            Object::Builtin(builtin) => write!(f, "builtin function {}", builtin.name),
+            Object::Native(native) => write!(f, "builtin function {}", native.name),
+            Object::Array(elements) => {
+                let Some(_guard) = PrintGuard::enter(Rc::as_ptr(elements) as usize) else {
+                    return write!(f, "[...]");
+                };
+                let elements: Vec<String> = elements.borrow().iter().map(|element| element.inspect()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(hash) => {
+                let Some(_guard) = PrintGuard::enter(Rc::as_ptr(hash) as usize) else {
+                    return write!(f, "{{...}}");
+                };
+                let pairs: Vec<String> = hash.borrow().entries().iter()
+                    .map(|(key, value)| format!("{}: {}", key.inspect(), value.inspect()))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Error(error) => write!(f, "{}: {}", error.kind, error.message),
+            Object::Ok(value) => write!(f, "Ok({})", value.inspect()),
+            Object::Err(error) => write!(f, "Err({})", error.inspect()),
+        }
+    }
+}
+
+thread_local! {
+    static PRINTING: RefCell<Vec<usize>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Marks a collection as being printed, so a collection containing itself prints as `[...]`.
+struct PrintGuard(usize);
+
+impl PrintGuard {
+    fn enter(address: usize) -> Option<PrintGuard> {
+        PRINTING.with(|printing| {
+            let mut printing = printing.borrow_mut();
+            if printing.contains(&address) {
+                return None;
+            }
+            printing.push(address);
+            Some(PrintGuard(address))
+        })
+    }
+}
+
+impl Drop for PrintGuard {
+    fn drop(&mut self) {
+        PRINTING.with(|printing| printing.borrow_mut().retain(|&address| address != self.0));
+    }
+}
+
+// the captured environment usually contains the function itself, so it is left out
+impl Debug for Function {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "Function({:?})", self.parameters)
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum ErrorKind {
+    /// An operation applied to a value of the wrong type.
+    TypeError,
+    /// An identifier with no binding.
+    NameError,
+    /// A call with the wrong number of arguments.
+    ArgumentError,
+    /// An array or string position outside of its bounds.
+    IndexError,
+    /// A hash key that is not present.
+    KeyError,
+    /// A value of the right type that is still unusable, like a string that is not a number.
+    ValueError,
+    /// A division by zero.
+    ArithmeticError,
+    /// A `break` or `continue` with no loop to leave.
+    SyntaxError,
+    /// Writing the output failed.
+    IoError,
+    /// Raised by `throw`.
+    Error,
+    /// Calls nested deeper than the configured maximum.
+    StackOverflow,
+    /// More steps than the configured limit, see `Limits`.
+    StepLimitExceeded,
+    /// More memory than the configured limit.
+    MemoryLimitExceeded,
+    /// Ran longer than the configured time limit.
+    Timeout,
+    /// An operation the configuration does not allow, like printing with I/O disabled.
+    PermissionError,
+    /// A module that cannot be found, read or checked, or that imports itself.
+    ImportError,
+}
+
+impl ErrorKind {
+    /// Errors that stop the script: `catch` and `finally` do not run for them.
+    pub fn is_fatal(&self) -> bool {
+        matches!(self, ErrorKind::StepLimitExceeded | ErrorKind::MemoryLimitExceeded | ErrorKind::Timeout | ErrorKind::PermissionError)
+    }
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        Debug::fmt(self, f)
+    }
+}
+
+/// A function call the error passed through on its way up.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Frame {
+    /// The function name, `<anonymous>` for functions not declared with `var`, `<module path>` for the
+    /// top level of an imported module.
+    pub function: String,
+    pub call_site: Span,
+    /// The module or prelude file of the function, which the locations inside the call are in.
+    pub file: Option<Rc<str>>,
+}
+
+/// Number of calls printed at each end of a long traceback.
+const TRACE_EDGE: usize = 10;
+
+#[derive(Debug, Clone)]
+pub struct RuntimeError {
+    pub kind: ErrorKind,
+    pub message: String,
+    /// Where the failing expression is, if it is known.
+    pub span: Option<Span>,
+    /// The calls the error passed through, innermost first.
+    pub frames: Vec<Frame>,
+    /// The value given to `throw`.
+    pub value: Option<Object>,
+}
+
+impl RuntimeError {
+    pub fn new(kind: ErrorKind, message: String) -> RuntimeError {
+        RuntimeError { kind, message, span: None, frames: vec![], value: None }
+    }
+
+    /// Describes the error with its location followed by the calls leading to it, outermost last.
+    /// Locations outside of the main program are prefixed with their file.
+    pub fn traceback(&self) -> String {
+        // a location is in the file of the innermost frame around it
+        let location = |span: Span, frame: usize| match self.frames.get(frame).and_then(|frame| frame.file.as_ref()) {
+            Some(file) => format!("{file}:{span}"),
+            None => span.to_string(),
+        };
+        let mut traceback = format!("{}: {}", self.kind, self.message);
+        if let Some(span) = self.span {
+            traceback.push_str(&format!(" at {}", location(span, 0)));
+        }
+        // deep recursion would print thousands of identical lines, only both ends are kept
+        let omitted = self.frames.len().saturating_sub(2 * TRACE_EDGE);
+        for (position, frame) in self.frames.iter().enumerate() {
+            if omitted > 0 && position == TRACE_EDGE {
+                traceback.push_str(&format!("\n    ... {omitted} more calls ..."));
+            }
+            if omitted > 0 && (TRACE_EDGE..TRACE_EDGE + omitted).contains(&position) {
+                continue;
+            }
+            let call_site = location(frame.call_site, position + 1);
+            traceback.push_str(&format!("\n    in {} called at {call_site}", frame.function));
+        }
+        traceback
+    }
+}
+
+/// Errors of native functions, see `IntoResult`.
+impl From<String> for RuntimeError {
+    fn from(message: String) -> Self {
+        RuntimeError::new(ErrorKind::Error, message)
+    }
+}
+
+impl From<&str> for RuntimeError {
+    fn from(message: &str) -> Self {
+        RuntimeError::new(ErrorKind::Error, message.to_string())
+    }
+}
+
+impl From<std::io::Error> for RuntimeError {
+    fn from(e: std::io::Error) -> Self {
+        RuntimeError::new(ErrorKind::IoError, e.to_string())
+    }
+}
+
+impl Display for RuntimeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{}", self.message)
+    }
+}
@@ -0,0 +1,888 @@
+use std::iter::Peekable;
+use std::rc::Rc;
+use std::str::Chars;
+use crate::ast::{Alignment, BlockStatement, Expression, FormatSpec, Program, Statement, StringPart};
+use crate::bigint::BigInt;
+use crate::lexer::Lexer;
+use crate::token::{lookup_identifier, Span, Token, TokenType};
+
+#[derive(PartialEq, PartialOrd, Debug, Clone, Copy)]
+enum Precedence {
+    Lowest,
+    Assign,
+    NullCoalescing,
+    Equals,
+    LessGreater,
+    Sum,
+    Product,
+    Prefix,
+    Call,
+    Index,
+}
+
+fn precedence_of(t_type: TokenType) -> Precedence {
+    match t_type {
+        TokenType::Assign => Precedence::Assign,
+        TokenType::NullCoalescing => Precedence::NullCoalescing,
+        TokenType::Equal | TokenType::NotEqual => Precedence::Equals,
+        TokenType::LessThan | TokenType::GreaterThan | TokenType::LessOrEqual | TokenType::GreaterOrEqual => {
+            Precedence::LessGreater
+        }
+        TokenType::Plus | TokenType::Minus => Precedence::Sum,
+        TokenType::Asterisk | TokenType::Slash => Precedence::Product,
+        TokenType::OpenParenthesis | TokenType::Question => Precedence::Call,
+        TokenType::OpenBracket | TokenType::Dot | TokenType::OptionalDot => Precedence::Index,
+        _ => Precedence::Lowest,
+    }
+}
+
+/// Reads `[alignment][0][width][.precision]`, returns None if anything else is left over.
+fn format_spec(literal: &str) -> Option<FormatSpec> {
+    let mut chars = literal.chars().peekable();
+    let alignment = match chars.peek() {
+        Some('<') => Some(Alignment::Left),
+        Some('^') => Some(Alignment::Center),
+        Some('>') => Some(Alignment::Right),
+        _ => None,
+    };
+    if alignment.is_some() {
+        chars.next();
+    }
+    let zero_pad = chars.next_if_eq(&'0').is_some();
+    fn number(chars: &mut Peekable<Chars>) -> Option<usize> {
+        let mut digits = String::new();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            digits.push(digit);
+        }
+        digits.parse::<usize>().ok()
+    }
+    let width = number(&mut chars);
+    let precision = match chars.next_if_eq(&'.') {
+        Some(_) => Some(number(&mut chars)?),
+        None => None,
+    };
+    if chars.next().is_some() {
+        return None;
+    }
+    Some(FormatSpec { alignment, zero_pad, width, precision })
+}
+
+pub struct Parser {
+    lexer: Lexer,
+    curr_token: Token,
+    peek_token: Token,
+    curr_span: Span,
+    peek_span: Span,
+    errors: Vec<String>,
+}
+
+impl Parser {
+    pub fn new(lexer: Lexer) -> Parser {
+        let mut parser = Parser {
+            lexer,
+            curr_token: Token { t_type: TokenType::Illegal, literal: "".to_string() },
+            peek_token: Token { t_type: TokenType::Illegal, literal: "".to_string() },
+            curr_span: Span::default(),
+            peek_span: Span::default(),
+            errors: vec![],
+        };
+        parser.next_token();
+        parser.next_token();
+        parser
+    }
+
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    fn next_token(&mut self) {
+        let next = self.lexer.next_token();
+        self.curr_token = std::mem::replace(&mut self.peek_token, next);
+        self.curr_span = std::mem::replace(&mut self.peek_span, self.lexer.span());
+    }
+
+    fn curr_token_is(&self, t_type: TokenType) -> bool {
+        self.curr_token.t_type == t_type
+    }
+
+    fn peek_token_is(&self, t_type: TokenType) -> bool {
+        self.peek_token.t_type == t_type
+    }
+
+    fn expect_peek(&mut self, t_type: TokenType) -> bool {
+        if self.peek_token_is(t_type) {
+            self.next_token();
+            true
+        } else {
+            self.errors.push(format!(
+                "expected next token to be {}, got {} instead", t_type, self.peek_token.t_type
+            ));
+            false
+        }
+    }
+
+    fn skip_semicolon(&mut self) {
+        if self.peek_token_is(TokenType::Semicolon) {
+            self.next_token();
+        }
+    }
+
+    pub fn parse_program(&mut self) -> Program {
+        let mut statements = vec![];
+        while !self.curr_token_is(TokenType::EndOfFile) {
+            if let Some(statement) = self.parse_statement() {
+                statements.push(statement);
+            }
+            self.next_token();
+        }
+        Program { statements: statements.into() }
+    }
+
+    fn parse_statement(&mut self) -> Option<Statement> {
+        match self.curr_token.t_type {
+            TokenType::Variable => self.parse_variable_statement(),
+            TokenType::Return => self.parse_return_statement(),
+            TokenType::While | TokenType::For => self.parse_loop_statement(None),
+            TokenType::Break | TokenType::Continue => self.parse_jump_statement(),
+            TokenType::Try => self.parse_try_statement(),
+            TokenType::Throw => self.parse_throw_statement(),
+            TokenType::Import => self.parse_import_statement(),
+            TokenType::Export => self.parse_export_statement(),
+            TokenType::Identifier if self.peek_token_is(TokenType::Colon) => self.parse_labeled_statement(),
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    fn parse_variable_statement(&mut self) -> Option<Statement> {
+        if !self.expect_peek(TokenType::Identifier) {
+            return None;
+        }
+        let name = self.curr_token.literal.clone();
+        let span = self.curr_span;
+        if !self.expect_peek(TokenType::Assign) {
+            return None;
+        }
+        self.next_token();
+        let mut value = self.parse_expression(Precedence::Lowest)?;
+        if let Expression::Function { name: function_name, .. } = &mut value {
+            *function_name = Some(name.clone());
+        }
+        self.skip_semicolon();
+        Some(Statement::Variable { name, value: Rc::new(value), span })
+    }
+
+    fn parse_return_statement(&mut self) -> Option<Statement> {
+        let span = self.curr_span;
+        // a bare return gives back null
+        if self.peek_token_is(TokenType::Semicolon) || self.peek_token_is(TokenType::CloseBrace)
+            || self.peek_token_is(TokenType::EndOfFile) {
+            self.skip_semicolon();
+            return Some(Statement::Return { value: Rc::new(Expression::Null), span });
+        }
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Some(Statement::Return { value: Rc::new(value), span })
+    }
+
+    fn parse_expression_statement(&mut self) -> Option<Statement> {
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Some(Statement::Expression(Rc::new(expression)))
+    }
+
+    fn parse_labeled_statement(&mut self) -> Option<Statement> {
+        let label = self.curr_token.literal.clone();
+        self.next_token();
+        if !self.peek_token_is(TokenType::While) && !self.peek_token_is(TokenType::For) {
+            self.errors.push(format!(
+                "expected a loop after label '{}', got {} instead", label, self.peek_token.t_type
+            ));
+            return None;
+        }
+        self.next_token();
+        self.parse_loop_statement(Some(label))
+    }
+
+    fn parse_loop_statement(&mut self, label: Option<String>) -> Option<Statement> {
+        if self.curr_token_is(TokenType::While) {
+            self.parse_while_statement(label)
+        } else {
+            self.parse_for_statement(label)
+        }
+    }
+
+    fn parse_while_statement(&mut self, label: Option<String>) -> Option<Statement> {
+        if !self.expect_peek(TokenType::OpenParenthesis) {
+            return None;
+        }
+        self.next_token();
+        let condition = Rc::new(self.parse_expression(Precedence::Lowest)?);
+        if !self.expect_peek(TokenType::CloseParenthesis) || !self.expect_peek(TokenType::OpenBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        Some(Statement::While { label, condition, body })
+    }
+
+    fn parse_for_statement(&mut self, label: Option<String>) -> Option<Statement> {
+        if !self.expect_peek(TokenType::OpenParenthesis) {
+            return None;
+        }
+        self.next_token();
+        if self.curr_token_is(TokenType::Identifier) && self.peek_token_is(TokenType::In) {
+            return self.parse_for_in_statement(label);
+        }
+
+        let init = if self.curr_token_is(TokenType::Semicolon) {
+            None
+        } else {
+            let init = match self.curr_token.t_type {
+                TokenType::Variable => self.parse_variable_statement()?,
+                _ => self.parse_expression_statement()?,
+            };
+            if !self.curr_token_is(TokenType::Semicolon) {
+                self.errors.push(format!(
+                    "expected ; after for loop initializer, got {} instead", self.peek_token.t_type
+                ));
+                return None;
+            }
+            Some(Box::new(init))
+        };
+
+        self.next_token();
+        let condition = if self.curr_token_is(TokenType::Semicolon) {
+            None
+        } else {
+            let condition = self.parse_expression(Precedence::Lowest)?;
+            if !self.expect_peek(TokenType::Semicolon) {
+                return None;
+            }
+            Some(Rc::new(condition))
+        };
+
+        self.next_token();
+        let step = if self.curr_token_is(TokenType::CloseParenthesis) {
+            None
+        } else {
+            let step = self.parse_expression(Precedence::Lowest)?;
+            if !self.expect_peek(TokenType::CloseParenthesis) {
+                return None;
+            }
+            Some(Rc::new(step))
+        };
+
+        if !self.expect_peek(TokenType::OpenBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        Some(Statement::For { label, init, condition, step, body })
+    }
+
+    fn parse_for_in_statement(&mut self, label: Option<String>) -> Option<Statement> {
+        let variable = self.curr_token.literal.clone();
+        self.next_token();
+        self.next_token();
+        let iterable = Rc::new(self.parse_expression(Precedence::Lowest)?);
+        if !self.expect_peek(TokenType::CloseParenthesis) || !self.expect_peek(TokenType::OpenBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        Some(Statement::ForIn { label, variable, iterable, body })
+    }
+
+    fn parse_jump_statement(&mut self) -> Option<Statement> {
+        let t_type = self.curr_token.t_type;
+        let label = if self.peek_token_is(TokenType::Identifier) {
+            self.next_token();
+            Some(self.curr_token.literal.clone())
+        } else {
+            None
+        };
+        self.skip_semicolon();
+        if t_type == TokenType::Break {
+            Some(Statement::Break(label))
+        } else {
+            Some(Statement::Continue(label))
+        }
+    }
+
+    fn parse_try_statement(&mut self) -> Option<Statement> {
+        if !self.expect_peek(TokenType::OpenBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        let catch = if self.peek_token_is(TokenType::Catch) {
+            self.next_token();
+            if !self.expect_peek(TokenType::OpenParenthesis) || !self.expect_peek(TokenType::Identifier) {
+                return None;
+            }
+            let name = self.curr_token.literal.clone();
+            if !self.expect_peek(TokenType::CloseParenthesis) || !self.expect_peek(TokenType::OpenBrace) {
+                return None;
+            }
+            Some((name, self.parse_block_statement()))
+        } else {
+            None
+        };
+        let finally = if self.peek_token_is(TokenType::Finally) {
+            self.next_token();
+            if !self.expect_peek(TokenType::OpenBrace) {
+                return None;
+            }
+            Some(self.parse_block_statement())
+        } else {
+            None
+        };
+        if catch.is_none() && finally.is_none() {
+            self.errors.push(format!(
+                "expected catch or finally after try block, got {} instead", self.peek_token.t_type
+            ));
+            return None;
+        }
+        Some(Statement::Try { body, catch, finally })
+    }
+
+    fn parse_throw_statement(&mut self) -> Option<Statement> {
+        let span = self.curr_span;
+        self.next_token();
+        let value = self.parse_expression(Precedence::Lowest)?;
+        self.skip_semicolon();
+        Some(Statement::Throw { value: Rc::new(value), span })
+    }
+
+    fn parse_import_statement(&mut self) -> Option<Statement> {
+        if !self.expect_peek(TokenType::String) {
+            return None;
+        }
+        let path = self.curr_token.literal.clone();
+        let span = self.curr_span;
+        let file_name = path.rsplit('/').next().unwrap_or_default();
+        let name = file_name.strip_suffix(".mk").unwrap_or(file_name).to_string();
+        let valid = name.chars().next().is_some_and(|c| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+            && lookup_identifier(&name) == TokenType::Identifier;
+        if !valid {
+            self.errors.push(format!("module name '{name}' of {path:?} is not an identifier"));
+            return None;
+        }
+        self.skip_semicolon();
+        Some(Statement::Import { path, name, span })
+    }
+
+    fn parse_export_statement(&mut self) -> Option<Statement> {
+        if !self.expect_peek(TokenType::Variable) {
+            return None;
+        }
+        let statement = self.parse_variable_statement()?;
+        Some(Statement::Export(Box::new(statement)))
+    }
+
+    fn parse_block_statement(&mut self) -> BlockStatement {
+        let mut statements = vec![];
+        self.next_token();
+        while !self.curr_token_is(TokenType::CloseBrace) && !self.curr_token_is(TokenType::EndOfFile) {
+            if let Some(statement) = self.parse_statement() {
+                statements.push(statement);
+            }
+            self.next_token();
+        }
+        if !self.curr_token_is(TokenType::CloseBrace) {
+            self.errors.push("expected } at the end of block, got EOF instead".to_string());
+        }
+        BlockStatement { statements: statements.into() }
+    }
+
+    fn parse_expression(&mut self, precedence: Precedence) -> Option<Expression> {
+        let mut left = self.parse_prefix()?;
+        while !self.peek_token_is(TokenType::Semicolon) && precedence < precedence_of(self.peek_token.t_type) {
+            self.next_token();
+            left = self.parse_infix(left)?;
+        }
+        Some(left)
+    }
+
+    fn parse_prefix(&mut self) -> Option<Expression> {
+        match self.curr_token.t_type {
+            TokenType::Identifier => {
+                Some(Expression::Identifier { name: self.curr_token.literal.clone(), span: self.curr_span })
+            }
+            TokenType::Integer => self.parse_integer_literal(),
+            TokenType::Double => self.parse_double_literal(),
+            TokenType::True => Some(Expression::Boolean(true)),
+            TokenType::False => Some(Expression::Boolean(false)),
+            TokenType::String => Some(Expression::String(self.curr_token.literal.clone())),
+            TokenType::StringStart => self.parse_interpolated_string(),
+            TokenType::Null => Some(Expression::Null),
+            TokenType::Bang | TokenType::Minus => {
+                let operator = self.curr_token.t_type;
+                let span = self.curr_span;
+                self.next_token();
+                let right = self.parse_expression(Precedence::Prefix)?;
+                Some(Expression::Prefix { operator, right: Rc::new(right), span })
+            }
+            TokenType::OpenParenthesis => {
+                self.next_token();
+                let expression = self.parse_expression(Precedence::Lowest)?;
+                if !self.expect_peek(TokenType::CloseParenthesis) {
+                    return None;
+                }
+                Some(expression)
+            }
+            TokenType::OpenBracket => {
+                let elements = self.parse_expression_list(TokenType::CloseBracket)?;
+                Some(Expression::Array(elements))
+            }
+            // blocks are only parsed where the grammar requires one (after if, else, loop headers and
+            // function parameters), a brace anywhere else opens a hash literal
+            TokenType::OpenBrace => self.parse_hash_literal(),
+            TokenType::If => self.parse_if_expression(),
+            TokenType::Function => self.parse_function_literal(),
+            TokenType::Illegal if self.curr_token.literal.chars().count() == 1 => {
+                self.errors.push(format!("unexpected character '{}' at {}", self.curr_token.literal, self.curr_span));
+                None
+            }
+            TokenType::Illegal => {
+                self.errors.push(format!("{} at {}", self.curr_token.literal, self.curr_span));
+                None
+            }
+            _ => {
+                self.errors.push(format!("no prefix parse function for {} found", self.curr_token.t_type));
+                None
+            }
+        }
+    }
+
+    fn parse_infix(&mut self, left: Expression) -> Option<Expression> {
+        let span = self.curr_span;
+        match self.curr_token.t_type {
+            TokenType::OpenParenthesis => {
+                let arguments = self.parse_expression_list(TokenType::CloseParenthesis)?;
+                Some(Expression::Call { function: Rc::new(left), arguments, span })
+            }
+            TokenType::OpenBracket => self.parse_index_expression(left),
+            TokenType::Question => Some(Expression::Propagate { value: Rc::new(left), span }),
+            TokenType::Dot | TokenType::OptionalDot => {
+                let optional = self.curr_token_is(TokenType::OptionalDot);
+                if !self.expect_peek(TokenType::Identifier) {
+                    return None;
+                }
+                let property = self.curr_token.literal.clone();
+                Some(Expression::Member { object: Rc::new(left), property, optional, span })
+            }
+            TokenType::Assign => {
+                let name = match left {
+                    Expression::Identifier { name, .. } => name,
+                    other => {
+                        self.errors.push(format!("invalid assignment target: {other}"));
+                        return None;
+                    }
+                };
+                self.next_token();
+                // assignment is right associative: a = b = c is a = (b = c)
+                let value = self.parse_expression(Precedence::Lowest)?;
+                Some(Expression::Assign { name, value: Rc::new(value), span })
+            }
+            operator => {
+                let precedence = precedence_of(operator);
+                self.next_token();
+                let right = self.parse_expression(precedence)?;
+                Some(Expression::Infix { operator, left: Rc::new(left), right: Rc::new(right), span })
+            }
+        }
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Option<Expression> {
+        let left = Rc::new(left);
+        let span = self.curr_span;
+        if self.peek_token_is(TokenType::Colon) {
+            return self.parse_slice_expression(left, None, span);
+        }
+        self.next_token();
+        let index = Rc::new(self.parse_expression(Precedence::Lowest)?);
+        if self.peek_token_is(TokenType::Colon) {
+            return self.parse_slice_expression(left, Some(index), span);
+        }
+        if !self.expect_peek(TokenType::CloseBracket) {
+            return None;
+        }
+        Some(Expression::Index { left, index, span })
+    }
+
+    fn parse_slice_expression(
+        &mut self,
+        left: Rc<Expression>,
+        start: Option<Rc<Expression>>,
+        span: Span,
+    ) -> Option<Expression> {
+        self.next_token();
+        let end = if self.peek_token_is(TokenType::CloseBracket) {
+            None
+        } else {
+            self.next_token();
+            Some(Rc::new(self.parse_expression(Precedence::Lowest)?))
+        };
+        if !self.expect_peek(TokenType::CloseBracket) {
+            return None;
+        }
+        Some(Expression::Slice { left, start, end, span })
+    }
+
+    fn parse_integer_literal(&mut self) -> Option<Expression> {
+        let literal = self.curr_token.literal.replace('_', "");
+        if let Ok(value) = literal.parse::<i64>() {
+            return Some(Expression::Integer(value));
+        }
+        // literals beyond 64 bits become big integers
+        match BigInt::parse(&literal) {
+            Some(value) => Some(Expression::BigInteger(value)),
+            None => {
+                self.errors.push(format!("could not parse {} as integer", self.curr_token.literal));
+                None
+            }
+        }
+    }
+
+    fn parse_double_literal(&mut self) -> Option<Expression> {
+        let literal = self.curr_token.literal.replace('_', "");
+        match literal.parse::<f64>() {
+            Ok(value) => Some(Expression::Double(value)),
+            Err(_) => {
+                self.errors.push(format!("could not parse {} as double", self.curr_token.literal));
+                None
+            }
+        }
+    }
+
+    fn parse_interpolated_string(&mut self) -> Option<Expression> {
+        let mut parts = vec![];
+        loop {
+            if !self.curr_token.literal.is_empty() {
+                parts.push(StringPart::Literal(self.curr_token.literal.clone()));
+            }
+            if self.curr_token_is(TokenType::StringEnd) {
+                return Some(Expression::Interpolated(parts));
+            }
+            self.next_token();
+            let expression = self.parse_expression(Precedence::Lowest)?;
+            let format = if self.peek_token_is(TokenType::FormatSpec) {
+                self.next_token();
+                Some(self.parse_format_spec()?)
+            } else {
+                None
+            };
+            parts.push(StringPart::Expression { expression: Rc::new(expression), format });
+            self.next_token();
+            if !self.curr_token_is(TokenType::StringMiddle) && !self.curr_token_is(TokenType::StringEnd) {
+                self.errors.push(format!(
+                    "expected }} to end the interpolation, got {} instead", self.curr_token.t_type
+                ));
+                return None;
+            }
+        }
+    }
+
+    fn parse_format_spec(&mut self) -> Option<FormatSpec> {
+        let spec = format_spec(&self.curr_token.literal);
+        if spec.is_none() {
+            self.errors.push(format!("invalid format specifier: {:?}", self.curr_token.literal));
+        }
+        spec
+    }
+
+    fn parse_hash_literal(&mut self) -> Option<Expression> {
+        let mut pairs = vec![];
+        while !self.peek_token_is(TokenType::CloseBrace) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            if !self.expect_peek(TokenType::Colon) {
+                return None;
+            }
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((Rc::new(key), Rc::new(value)));
+            if !self.peek_token_is(TokenType::CloseBrace) && !self.expect_peek(TokenType::Comma) {
+                return None;
+            }
+        }
+        self.next_token();
+        Some(Expression::Hash(pairs))
+    }
+
+    fn parse_if_expression(&mut self) -> Option<Expression> {
+        if !self.expect_peek(TokenType::OpenParenthesis) {
+            return None;
+        }
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        if !self.expect_peek(TokenType::CloseParenthesis) || !self.expect_peek(TokenType::OpenBrace) {
+            return None;
+        }
+        let consequence = self.parse_block_statement();
+        let alternative = if self.peek_token_is(TokenType::Else) {
+            self.next_token();
+            if !self.expect_peek(TokenType::OpenBrace) {
+                return None;
+            }
+            Some(self.parse_block_statement())
+        } else {
+            None
+        };
+        Some(Expression::If { condition: Rc::new(condition), consequence, alternative })
+    }
+
+    fn parse_function_literal(&mut self) -> Option<Expression> {
+        if !self.expect_peek(TokenType::OpenParenthesis) {
+            return None;
+        }
+        let (parameters, parameter_spans) = self.parse_function_parameters()?;
+        if !self.expect_peek(TokenType::OpenBrace) {
+            return None;
+        }
+        let body = self.parse_block_statement();
+        Some(Expression::Function { name: None, parameters, parameter_spans, body })
+    }
+
+    /// Returns the parameter names and where each of them is declared.
+    fn parse_function_parameters(&mut self) -> Option<(Vec<String>, Vec<Span>)> {
+        let mut parameters = vec![];
+        let mut spans = vec![];
+        if self.peek_token_is(TokenType::CloseParenthesis) {
+            self.next_token();
+            return Some((parameters, spans));
+        }
+        if !self.expect_peek(TokenType::Identifier) {
+            return None;
+        }
+        parameters.push(self.curr_token.literal.clone());
+        spans.push(self.curr_span);
+        while self.peek_token_is(TokenType::Comma) {
+            self.next_token();
+            if !self.expect_peek(TokenType::Identifier) {
+                return None;
+            }
+            parameters.push(self.curr_token.literal.clone());
+            spans.push(self.curr_span);
+        }
+        if !self.expect_peek(TokenType::CloseParenthesis) {
+            return None;
+        }
+        Some((parameters, spans))
+    }
+
+    fn parse_expression_list(&mut self, end: TokenType) -> Option<Vec<Rc<Expression>>> {
+        let mut list = vec![];
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Some(list);
+        }
+        self.next_token();
+        list.push(Rc::new(self.parse_expression(Precedence::Lowest)?));
+        while self.peek_token_is(TokenType::Comma) {
+            self.next_token();
+            self.next_token();
+            list.push(Rc::new(self.parse_expression(Precedence::Lowest)?));
+        }
+        if !self.expect_peek(end) {
+            return None;
+        }
+        Some(list)
+    }
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use std::rc::Rc;
+    use crate::ast::{Expression, Program, Statement};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::token::{Span, TokenType};
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "parser has errors: {:?}", parser.errors());
+        program
+    }
+
+    #[test]
+    fn test_variable_statements() {
+        let program = parse("var x = 5; var y = 2.5; var foo_bar = y;");
+
+        let expected: Vec<Statement> = vec![
+            Statement::Variable { name: "x".to_string(), value: Rc::new(Expression::Integer(5)), span: Span { line: 1, column: 5 } },
+            Statement::Variable { name: "y".to_string(), value: Rc::new(Expression::Double(2.5)), span: Span { line: 1, column: 16 } },
+            Statement::Variable {
+                name: "foo_bar".to_string(),
+                value: Rc::new(Expression::Identifier { name: "y".to_string(), span: Span { line: 1, column: 39 } }),
+                span: Span { line: 1, column: 29 },
+            },
+        ];
+        assert_eq!(expected, *program.statements);
+    }
+
+    #[test]
+    fn test_operator_precedence() {
+        let tests: Vec<(&str, &str)> = vec![
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b - c", "((a + b) - c)"),
+            ("a + b * c + d / e - f", "(((a + (b * c)) + (d / e)) - f)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            ("3 + 4 * 5 == 3 * 1 + 4 * 5", "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))"),
+            ("(5 + 5) * 2", "((5 + 5) * 2)"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            ("x = y = 1 + 2", "(x = (y = (1 + 2)))"),
+            ("-9223372036854775808 + 1_000_000_000_000_000_000_000", "((-9223372036854775808) + 1000000000000000000000)"),
+            ("a * [1, 2, 3, 4][b * c] * d", "((a * ([1, 2, 3, 4][(b * c)])) * d)"),
+            ("add(a * b[2], b[1], 2 * [1, 2][1])", "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))"),
+            ("a[1:2][-1]", "((a[1:2])[(-1)])"),
+            ("a[:n - 1] + a[1:] + a[:]", "(((a[:(n - 1)]) + (a[1:])) + (a[:]))"),
+            ("{}", "{}"),
+            ("a.b.c", "((a.b).c)"),
+            ("a?.b(1)[2]", "((a?.b)(1)[2])"),
+            ("a ?? b ?? c", "((a ?? b) ?? c)"),
+            ("x = a?.b ?? 1 + 2", "(x = ((a?.b) ?? (1 + 2)))"),
+            ("a ?? b == c", "(a ?? (b == c))"),
+            ("-a.b", "(-(a.b))"),
+            ("a.b(c)?", "((a.b)(c)?)"),
+            ("-a?", "(-(a?))"),
+            ("a? ?? b?[1]", "((a?) ?? ((b?)[1]))"),
+            ("a + b?", "(a + (b?))"),
+            ("{\"one\": 1, 2: 1 + 1, true: {}}[\"one\"]", "({\"one\": 1, 2: (1 + 1), true: {}}[\"one\"])"),
+            ("\"a ${b + c * d} e ${f:<08.2}\"", "\"a ${(b + (c * d))} e ${f:<08.2}\""),
+            ("\"${\"${x}\"}\\$\\n\"", "\"${\"${x}\"}\\$\\n\""),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, parse(input).to_string(), "wrong precedence for {input}");
+        }
+    }
+
+    #[test]
+    fn test_if_and_function_expressions() {
+        let program = parse("if (x < y) { x } else { y }; func(x, y) { return x + y; }(1, 2)");
+
+        assert_eq!("if (x < y) { x } else { y }", program.statements[0].to_string());
+        assert_eq!("func(x, y) { return (x + y); }(1, 2)", program.statements[1].to_string());
+    }
+
+    #[test]
+    fn test_loop_statements() {
+        let tests: Vec<(&str, &str)> = vec![
+            ("while (x < 10) { x = x + 1; }", "while ((x < 10)) { (x = (x + 1)) }"),
+            ("for (x in items) { continue; }", "for (x in items) { continue; }"),
+            (
+                "for (var i = 0; i < 10; i = i + 1) { break; }",
+                "for (var i = 0; (i < 10); (i = (i + 1))) { break; }",
+            ),
+            ("for (;;) { }", "for (;;) { }"),
+            (
+                "outer: while (true) { for (x in y) { break outer; } }",
+                "outer: while (true) { for (x in y) { break outer; } }",
+            ),
+            ("inner: for (;;) { continue inner; }", "inner: for (;;) { continue inner; }"),
+        ];
+
+        for (input, expected) in tests {
+            assert_eq!(expected, parse(input).to_string(), "wrong loop for {input}");
+        }
+    }
+
+    #[test]
+    fn test_loop_ast() {
+        let program = parse("outer: for (x in y) { break outer; }");
+
+        assert_eq!(
+            vec![Statement::ForIn {
+                label: Some("outer".to_string()),
+                variable: "x".to_string(),
+                iterable: Rc::new(Expression::Identifier { name: "y".to_string(), span: Span { line: 1, column: 18 } }),
+                body: crate::ast::BlockStatement {
+                    statements: Rc::new([Statement::Break(Some("outer".to_string()))])
+                },
+            }],
+            *program.statements
+        );
+    }
+
+    #[test]
+    fn test_parser_errors() {
+        let tests: Vec<(&str, &str)> = vec![
+            ("var = 5;", "expected next token to be Identifier, got = instead"),
+            ("outer: x + 1", "expected a loop after label 'outer', got Identifier instead"),
+            ("5 = 6", "invalid assignment target: 5"),
+            ("while x { }", "expected next token to be (, got Identifier instead"),
+            ("for (var i = 0) { }", "expected ; after for loop initializer, got ) instead"),
+            ("a[1 2]", "expected next token to be ], got Integer instead"),
+            ("a[1:2:3]", "expected next token to be ], got : instead"),
+            ("[1, 2", "expected next token to be ], got EOF instead"),
+            ("{\"a\" 1}", "expected next token to be :, got Integer instead"),
+            ("{\"a\": 1 \"b\": 2}", "expected next token to be ,, got String instead"),
+            ("{\"a\": 1,", "no prefix parse function for EOF found"),
+            ("a.1", "expected next token to be Identifier, got Integer instead"),
+            ("a?.", "expected next token to be Identifier, got EOF instead"),
+            ("\"${a b}\"", "expected } to end the interpolation, got Identifier instead"),
+            ("\"${}\"", "no prefix parse function for StringEnd found"),
+            ("\"${a:<x}\"", "invalid format specifier: \"<x\""),
+            ("\"${a:.}\"", "invalid format specifier: \".\""),
+            ("\"${a", "expected } to end the interpolation, got EOF instead"),
+            ("x = 1 @", "unexpected character '@' at 1:7"),
+            ("try { }", "expected catch or finally after try block, got EOF instead"),
+            ("try { } catch e { }", "expected next token to be (, got Identifier instead"),
+            ("try { } catch (1) { }", "expected next token to be Identifier, got Integer instead"),
+            ("try { } finally x", "expected next token to be {, got Identifier instead"),
+            ("throw;", "no prefix parse function for ; found"),
+            ("var s = \"\"\"\n  never closed", "unterminated multi-line string at 1:9"),
+            ("f(1,\n   r#\"raw\")", "unterminated raw string at 2:4"),
+            ("import lib", "expected next token to be String, got Identifier instead"),
+            ("import \"lib/my-mod\"", "module name 'my-mod' of \"lib/my-mod\" is not an identifier"),
+            ("import \"lib/if.mk\"", "module name 'if' of \"lib/if.mk\" is not an identifier"),
+            ("export x = 1", "expected next token to be Variable, got Identifier instead"),
+        ];
+
+        for (input, expected) in tests {
+            let mut parser = Parser::new(Lexer::new(input));
+            parser.parse_program();
+            assert_eq!(Some(&expected.to_string()), parser.errors().first(), "wrong error for {input}");
+        }
+    }
+
+    #[test]
+    fn test_module_statements() {
+        let program = parse("import \"../lib/math.mk\"; export var x = math.pi");
+
+        let span = Span { line: 1, column: 8 };
+        let import = Statement::Import { path: "../lib/math.mk".to_string(), name: "math".to_string(), span };
+        assert_eq!(import, program.statements[0]);
+        assert_eq!("export var x = (math.pi);", program.statements[1].to_string());
+        assert_eq!("import \"lib/_util\";", parse("import \"lib/_util\"").to_string());
+    }
+
+    #[test]
+    fn test_return_statements() {
+        let program = parse("return; return 5; func() { return }");
+
+        let span = Span { line: 1, column: 1 };
+        assert_eq!(Statement::Return { value: Rc::new(Expression::Null), span }, program.statements[0]);
+        let span = Span { line: 1, column: 9 };
+        assert_eq!(Statement::Return { value: Rc::new(Expression::Integer(5)), span }, program.statements[1]);
+        assert_eq!("func() { return null; }", program.statements[2].to_string());
+    }
+
+    #[test]
+    fn test_prefix_operator() {
+        let program = parse("!true");
+
+        assert_eq!(
+            vec![Statement::Expression(Rc::new(Expression::Prefix {
+                operator: TokenType::Bang,
+                right: Rc::new(Expression::Boolean(true)),
+                span: Span { line: 1, column: 1 },
+            }))],
+            *program.statements
+        );
+    }
+}
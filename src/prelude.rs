@@ -0,0 +1,119 @@
+//! The standard library, written in the language itself and bundled into the crate. Every `Interpreter`
+//! runs it before anything else unless `Config::prelude` is off. Each file has its own top level, which
+//! sees only the builtins, and the functions it exports are globals of the main program and of modules.
+//! Scripts may shadow them without breaking the other prelude functions.
+//!
+//! - `list.mk`: `map`, `filter`, `reduce`, `any`, `all`, `sum`, `reverse`, `sort`, `sort_by`, `zip` and
+//!   `enumerate` over arrays. They return new arrays, sorting is stable. All but `sum` and `reverse` are
+//!   the builtins of the same name.
+//! - `string.mk`: `pad_left`, `pad_right` and `center`, padding a string to a width with a fill string.
+//! - `math.mk`: `abs`, `min`, `max` and `clamp`.
+
+/// The prelude files by name, in the order they run.
+pub const FILES: &[(&str, &str)] = &[
+    ("list.mk", include_str!("prelude/list.mk")),
+    ("string.mk", include_str!("prelude/string.mk")),
+    ("math.mk", include_str!("prelude/math.mk")),
+];
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use crate::evaluator::{Config, Limits, Truthiness};
+    use crate::interpreter::{Error, Interpreter};
+
+    fn assert_evaluates_to(interpreter: &mut Interpreter, tests: Vec<(&str, &str)>) {
+        for (input, expected) in tests {
+            match interpreter.eval(input) {
+                Ok(value) => assert_eq!(expected, value.to_string(), "wrong value for {input}"),
+                Err(e) => panic!("unexpected error for {input}: {e}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_list() {
+        assert_evaluates_to(&mut Interpreter::new(), vec![
+            ("map([1, 2, 3], func(x) { x * 2 })", "[2, 4, 6]"),
+            ("map([], func(x) { x })", "[]"),
+            ("filter([1, 2, 3, 4], func(x) { x / 2 * 2 == x })", "[2, 4]"),
+            ("reduce([1, 2, 3], func(total, x) { total * 10 + x }, 0)", "123"),
+            ("reduce([], func(total, x) { total + x }, 7)", "7"),
+            ("[any([1, 5], func(x) { x > 4 }), any([1, 2], func(x) { x > 4 }), any([], func(x) { true })]", "[true, false, false]"),
+            ("[all([5, 6], func(x) { x > 4 }), all([5, 1], func(x) { x > 4 }), all([], func(x) { false })]", "[true, false, true]"),
+            ("[sum([1, 2, 3]), sum([]), sum([0.5, 1])]", "[6, 0, 1.5]"),
+            ("reverse([1, 2, 3])", "[3, 2, 1]"),
+            ("reverse([])", "[]"),
+            ("var items = [3, 1, 2]; [sort(items), items]", "[[1, 2, 3], [3, 1, 2]]"),
+            (r#"sort(["b", "c", "a"])"#, r#"["a", "b", "c"]"#),
+            ("sort([])", "[]"),
+            (
+                r#"sort_by([["b", 2], ["a", 1], ["c", 2], ["d", 1]], func(pair) { pair[1] })"#,
+                r#"[["a", 1], ["d", 1], ["b", 2], ["c", 2]]"#,
+            ),
+            (r#"zip([1, 2, 3], ["a", "b"])"#, r#"[[1, "a"], [2, "b"]]"#),
+            (r#"enumerate(["a", "b"])"#, r#"[[0, "a"], [1, "b"]]"#),
+        ]);
+    }
+
+    #[test]
+    fn test_string() {
+        assert_evaluates_to(&mut Interpreter::new(), vec![
+            (r#"pad_left("7", 3, "0")"#, "007"),
+            (r#"pad_left("long", 2, " ")"#, "long"),
+            (r#"pad_right("ab", 4, ".")"#, "ab.."),
+            (r#"pad_right("ab", 2, ".")"#, "ab"),
+            (r#"center("ab", 5, "*")"#, "*ab**"),
+            (r#"center("abc", 1, "*")"#, "abc"),
+        ]);
+    }
+
+    #[test]
+    fn test_math() {
+        assert_evaluates_to(&mut Interpreter::new(), vec![
+            ("[abs(-3), abs(2), abs(-1.5)]", "[3, 2, 1.5]"),
+            ("[min(1, 2), min(2, 1), max(1, 2), max(2.5, 1)]", "[1, 1, 2, 2.5]"),
+            ("[clamp(5, 0, 3), clamp(-1, 0, 3), clamp(2, 0, 3)]", "[3, 0, 2]"),
+        ]);
+    }
+
+    #[test]
+    fn test_prelude_config() {
+        let mut interpreter = Interpreter::new();
+        assert_evaluates_to(&mut interpreter, vec![
+            ("var sum = 10; sum", "10"),
+            ("var map = func(items, f) { \"mine\" }; map([1], len)", "mine"),
+            ("var reverse = func(items) { \"mine\" }; reverse([1])", "mine"),
+        ]);
+
+        // the prelude uses its own functions and the builtins, whatever the scripts declare
+        let mut interpreter = Interpreter::new();
+        assert_evaluates_to(&mut interpreter, vec![
+            ("var max = 100; var min = 0; clamp(5, 0, 3)", "3"),
+            ("var repeat = 1; var len = 2; var reduce = 3; [pad_left(\"7\", 3, \"0\"), sum([1, 2])]", "[\"007\", 3]"),
+            ("var range = 4; reverse([1, 2])", "[2, 1]"),
+        ]);
+        match interpreter.eval("\nclamp(\"a\", 0, 3)") {
+            Err(e) => assert_eq!(
+                "TypeError: type mismatch: INTEGER > STRING at prelude/math.mk:10:11\n    in max called at prelude/math.mk:14:12\n    in clamp called at 2:6",
+                e.to_string(),
+            ),
+            other => panic!("expected an error, got {other:?}"),
+        }
+
+        let mut interpreter = Interpreter::with_config(Config { prelude: false, ..Config::default() });
+        match interpreter.eval("sum([1])") {
+            Err(Error::Resolve(diagnostics)) => assert_eq!("identifier not found: sum", diagnostics[0].message),
+            other => panic!("expected a resolve error, got {other:?}"),
+        }
+
+        // limits apply to the scripts only, the prelude loads under any configuration
+        let limits = Limits { max_steps: Some(1), max_heap_bytes: Some(1), ..Limits::default() };
+        let config = Config { truthiness: Truthiness::Strict, limits, ..Config::default() };
+        let mut interpreter = Interpreter::with_config(config);
+        assert!(interpreter.eval("sort([2, 1])").is_err());
+        assert!(interpreter.get_global("sort").is_some());
+        assert!(interpreter.get_global("reverse").is_some());
+    }
+}
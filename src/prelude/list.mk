@@ -0,0 +1,21 @@
+export var map = map;
+export var filter = filter;
+export var reduce = reduce;
+export var any = any;
+export var all = all;
+export var sort = sort;
+export var sort_by = sort_by;
+export var zip = zip;
+export var enumerate = enumerate;
+
+export var sum = func(items) {
+    reduce(items, func(total, item) { total + item }, 0)
+};
+
+export var reverse = func(items) {
+    var result = [];
+    for (i in range(len(items) - 1, -1, -1)) {
+        push(result, items[i]);
+    }
+    result
+};
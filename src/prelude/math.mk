@@ -0,0 +1,15 @@
+export var abs = func(x) {
+    if (x < 0) { -x } else { x }
+};
+
+export var min = func(a, b) {
+    if (b < a) { b } else { a }
+};
+
+export var max = func(a, b) {
+    if (b > a) { b } else { a }
+};
+
+export var clamp = func(x, low, high) {
+    min(max(x, low), high)
+};
@@ -0,0 +1,24 @@
+export var pad_left = func(text, width, fill) {
+    var missing = width - len(text);
+    if (missing <= 0) {
+        return text;
+    }
+    repeat(fill, missing) + text
+};
+
+export var pad_right = func(text, width, fill) {
+    var missing = width - len(text);
+    if (missing <= 0) {
+        return text;
+    }
+    text + repeat(fill, missing)
+};
+
+export var center = func(text, width, fill) {
+    var missing = width - len(text);
+    if (missing <= 0) {
+        return text;
+    }
+    var left = missing / 2;
+    repeat(fill, left) + text + repeat(fill, missing - left)
+};
@@ -1,25 +1,46 @@
 use std::io::{Stdin, Stdout, Write};
-use crate::lexer::Lexer;
-use crate::token::TokenType;
+use interpreter_in_rust::evaluator::Config;
+use interpreter_in_rust::interpreter::Interpreter;
 
-pub fn start(stdin: Stdin, mut stdout: Stdout) {
+pub fn start(stdin: Stdin, mut stdout: Stdout, config: Config) {
+    let mut interpreter = Interpreter::with_config(config);
     loop {
         write!(stdout, ">> ").expect("unable to display prompt string '>>'");
         stdout.flush().expect("unable to flush stdout");
 
         let mut input = String::new();
-        if let Err(e) = stdin.read_line(&mut input) {
-            writeln!(stdout, "Error: {e}").expect("unable to display the error message");
-            return;
+        match stdin.read_line(&mut input) {
+            Ok(0) => return,
+            Ok(_) => {}
+            Err(e) => {
+                writeln!(stdout, "Error: {e}").expect("unable to display the error message");
+                return;
+            }
         }
 
-        let mut lexer = Lexer::new(input.as_str());
-        loop {
-            let token = lexer.next_token();
-            if token.t_type == TokenType::EndOfFile {
-                break;
-            }
-            writeln!(stdout, "{token:?}").expect("unable to display token");
+        let result = interpreter.eval(input.as_str());
+        for warning in interpreter.warnings() {
+            writeln!(stdout, "{warning}").expect("unable to display the diagnostic");
+        }
+        match result {
+            Ok(value) => writeln!(stdout, "{value}").expect("unable to display the result"),
+            Err(e) => writeln!(stdout, "{e}").expect("unable to display the error message"),
+        }
+    }
+}
+
+/// Runs a whole script file, returns the process exit code.
+pub fn run_file(path: &str, config: Config) -> i32 {
+    let mut interpreter = Interpreter::with_config(config);
+    let result = interpreter.eval_file(path);
+    for warning in interpreter.warnings() {
+        eprintln!("{warning}");
+    }
+    match result {
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("{e}");
+            1
         }
     }
-}
\ No newline at end of file
+}
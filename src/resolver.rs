@@ -0,0 +1,450 @@
+use std::cell::RefCell;
+use std::fmt::{Display, Formatter, Result};
+use std::rc::Rc;
+use crate::ast::{BlockStatement, Expression, Program, Statement, StringPart};
+use crate::builtins;
+use crate::token::Span;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Severity {
+    /// The program is not run.
+    Error,
+    Warning,
+}
+
+/// A problem found before running the program.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result {
+        write!(f, "{:?}: {} at {}", self.severity, self.message, self.span)
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Declaration {
+    Variable,
+    Parameter,
+    /// Loop and catch variables, which are not reported when unused.
+    Binding,
+}
+
+struct Variable {
+    name: String,
+    span: Span,
+    declaration: Declaration,
+    used: bool,
+}
+
+type ScopeRef = Rc<RefCell<Vec<Variable>>>;
+
+/// Checks programs before they run: reports undeclared identifiers, variables declared twice in a scope
+/// and `return` outside of a function as errors, and unused variables and parameters as warnings.
+///
+/// Function bodies are checked once the whole program has been seen, because a function may use
+/// variables declared after it, as long as they exist by the time it is called.
+pub struct Resolver {
+    globals: ScopeRef,
+}
+
+/// What the resolver found in one program.
+#[derive(Debug)]
+pub struct Resolved {
+    /// The diagnostics in source order.
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl Resolved {
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics.iter().any(|diagnostic| diagnostic.severity == Severity::Error)
+    }
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Resolver {
+    pub fn new() -> Resolver {
+        Resolver {
+            globals: Rc::new(RefCell::new(vec![])),
+        }
+    }
+
+    /// Checks a program. Top-level declarations of a program without errors stay known to the programs
+    /// resolved after it, like the lines typed into the REPL.
+    pub fn resolve(&mut self, program: &Program) -> Resolved {
+        let first_global = self.globals.borrow().len();
+        let mut walker = Walker {
+            scopes: vec![self.globals.clone()],
+            in_function: false,
+            first_global,
+            pending: vec![],
+            closed: vec![],
+            diagnostics: vec![],
+        };
+        walker.statements(&program.statements);
+        while let Some(function) = walker.pending.pop() {
+            walker.function_body(function);
+        }
+        walker.report_unused();
+
+        let mut diagnostics = walker.diagnostics;
+        diagnostics.sort_by_key(|diagnostic| (diagnostic.span.line, diagnostic.span.column));
+        let resolved = Resolved { diagnostics };
+        if resolved.has_errors() {
+            self.globals.borrow_mut().truncate(first_global);
+        }
+        resolved
+    }
+
+    /// The number of top-level declarations known so far, see `forget_globals`.
+    pub fn global_count(&self) -> usize {
+        self.globals.borrow().len()
+    }
+
+    /// Forgets the top-level declarations made after the first `count` for which `bound` is false. A
+    /// program that fails while running may not get to declare every variable the resolver saw in it.
+    pub fn forget_globals(&mut self, count: usize, bound: impl Fn(&str) -> bool) {
+        let mut globals = self.globals.borrow_mut();
+        let mut position = 0;
+        globals.retain(|variable| {
+            position += 1;
+            position <= count || bound(&variable.name)
+        });
+    }
+
+    /// Makes a top-level variable defined by the host known to the programs resolved after it.
+    pub fn declare_global(&mut self, name: &str) {
+        let mut globals = self.globals.borrow_mut();
+        if !globals.iter().any(|variable| variable.name == name) {
+            let declaration = Declaration::Binding;
+            globals.push(Variable { name: name.to_string(), span: Span::default(), declaration, used: true });
+        }
+    }
+}
+
+/// A function literal waiting for its body to be checked, with the scopes it was created in.
+struct PendingFunction<'a> {
+    scopes: Vec<ScopeRef>,
+    parameters: &'a [String],
+    parameter_spans: &'a [Span],
+    body: &'a BlockStatement,
+}
+
+struct Walker<'a> {
+    /// The scopes the walk is in, the top level first.
+    scopes: Vec<ScopeRef>,
+    in_function: bool,
+    /// Globals from before this program, which it may declare again.
+    first_global: usize,
+    pending: Vec<PendingFunction<'a>>,
+    /// Scopes that were left, checked for unused variables at the end.
+    closed: Vec<ScopeRef>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> Walker<'a> {
+    fn error(&mut self, message: String, span: Span) {
+        self.diagnostics.push(Diagnostic { severity: Severity::Error, message, span });
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(Rc::new(RefCell::new(vec![])));
+    }
+
+    fn leave_scope(&mut self) {
+        let scope = self.scopes.pop().expect("the top level is never left");
+        self.closed.push(scope);
+    }
+
+    fn declare(&mut self, name: &str, span: Span, declaration: Declaration) {
+        let first = if self.scopes.len() == 1 { self.first_global } else { 0 };
+        let scope = self.scopes.last().expect("there is always a scope").clone();
+        let mut scope = scope.borrow_mut();
+        if scope[first..].iter().any(|variable| variable.name == name) {
+            self.error(format!("'{name}' is already declared in this scope"), span);
+            return;
+        }
+        scope.push(Variable { name: name.to_string(), span, declaration, used: false });
+    }
+
+    /// Marks what `name` refers to as used, assignments do not count as using a variable.
+    fn lookup(&mut self, name: &str, span: Span, used: bool) {
+        for scope in self.scopes.iter().rev() {
+            // the latest declaration wins, the REPL may declare a global again
+            if let Some(variable) = scope.borrow_mut().iter_mut().rev().find(|variable| variable.name == name) {
+                variable.used |= used;
+                return;
+            }
+        }
+        if builtins::lookup(name).is_none() {
+            self.error(format!("identifier not found: {name}"), span);
+        }
+    }
+
+    fn function_body(&mut self, function: PendingFunction<'a>) {
+        self.scopes = function.scopes;
+        self.in_function = true;
+        // parameters and the variables of the body share a scope, as they do when the function is called
+        self.enter_scope();
+        for (parameter, span) in function.parameters.iter().zip(function.parameter_spans) {
+            self.declare(parameter, *span, Declaration::Parameter);
+        }
+        self.statements(&function.body.statements);
+        self.leave_scope();
+    }
+
+    fn report_unused(&mut self) {
+        for scope in &self.closed {
+            for variable in scope.borrow().iter() {
+                let kind = match variable.declaration {
+                    Declaration::Variable => "variable",
+                    Declaration::Parameter => "parameter",
+                    Declaration::Binding => continue,
+                };
+                // a leading underscore marks a variable as unused on purpose
+                if !variable.used && !variable.name.starts_with('_') {
+                    self.diagnostics.push(Diagnostic {
+                        severity: Severity::Warning,
+                        message: format!("unused {kind} '{}'", variable.name),
+                        span: variable.span,
+                    });
+                }
+            }
+        }
+    }
+
+    fn statements(&mut self, statements: &'a [Statement]) {
+        for statement in statements {
+            self.statement(statement);
+        }
+    }
+
+    fn block(&mut self, block: &'a BlockStatement) {
+        self.enter_scope();
+        self.statements(&block.statements);
+        self.leave_scope();
+    }
+
+    fn statement(&mut self, statement: &'a Statement) {
+        match statement {
+            // the value is evaluated before the variable exists
+            Statement::Variable { name, value, span } => {
+                self.expression(value);
+                self.declare(name, *span, Declaration::Variable);
+            }
+            Statement::Return { value, span } => {
+                if !self.in_function {
+                    self.error("return outside of a function".to_string(), *span);
+                }
+                self.expression(value);
+            }
+            Statement::Expression(expression) => self.expression(expression),
+            Statement::While { condition, body, .. } => {
+                self.expression(condition);
+                self.block(body);
+            }
+            Statement::ForIn { variable, iterable, body, .. } => {
+                self.expression(iterable);
+                self.enter_scope();
+                self.declare(variable, Span::default(), Declaration::Binding);
+                self.block(body);
+                self.leave_scope();
+            }
+            Statement::For { init, condition, step, body, .. } => {
+                self.enter_scope();
+                if let Some(init) = init {
+                    self.statement(init);
+                }
+                if let Some(condition) = condition {
+                    self.expression(condition);
+                }
+                self.block(body);
+                if let Some(step) = step {
+                    self.expression(step);
+                }
+                self.leave_scope();
+            }
+            Statement::Break(_) | Statement::Continue(_) => {}
+            Statement::Try { body, catch, finally } => {
+                self.block(body);
+                if let Some((name, handler)) = catch {
+                    self.enter_scope();
+                    self.declare(name, Span::default(), Declaration::Binding);
+                    self.block(handler);
+                    self.leave_scope();
+                }
+                if let Some(finally) = finally {
+                    self.block(finally);
+                }
+            }
+            Statement::Throw { value, .. } => self.expression(value),
+            Statement::Import { name, span, .. } => {
+                // modules are found relative to the file running, which a function may be called from
+                if self.in_function {
+                    self.error("import inside a function".to_string(), *span);
+                }
+                self.declare(name, *span, Declaration::Binding);
+            }
+            Statement::Export(statement) => {
+                if let (true, Statement::Variable { span, .. }) = (self.scopes.len() > 1, statement.as_ref()) {
+                    self.error("export outside of the top level".to_string(), *span);
+                }
+                self.statement(statement);
+            }
+        }
+    }
+
+    fn expression(&mut self, expression: &'a Expression) {
+        match expression {
+            Expression::Identifier { name, span } => self.lookup(name, *span, true),
+            Expression::Integer(_)
+            | Expression::BigInteger(_)
+            | Expression::Double(_)
+            | Expression::Boolean(_)
+            | Expression::String(_)
+            | Expression::Null => {}
+            Expression::Interpolated(parts) => {
+                for part in parts {
+                    if let StringPart::Expression { expression, .. } = part {
+                        self.expression(expression);
+                    }
+                }
+            }
+            Expression::Prefix { right, .. } => self.expression(right),
+            Expression::Infix { left, right, .. } => {
+                self.expression(left);
+                self.expression(right);
+            }
+            Expression::Assign { name, value, span } => {
+                self.expression(value);
+                self.lookup(name, *span, false);
+            }
+            Expression::If { condition, consequence, alternative } => {
+                self.expression(condition);
+                self.block(consequence);
+                if let Some(alternative) = alternative {
+                    self.block(alternative);
+                }
+            }
+            Expression::Function { parameters, parameter_spans, body, .. } => self.pending.push(PendingFunction {
+                scopes: self.scopes.clone(),
+                parameters,
+                parameter_spans,
+                body,
+            }),
+            Expression::Call { function, arguments, .. } => {
+                self.expression(function);
+                self.expressions(arguments);
+            }
+            Expression::Array(elements) => self.expressions(elements),
+            Expression::Hash(pairs) => {
+                for (key, value) in pairs {
+                    self.expression(key);
+                    self.expression(value);
+                }
+            }
+            Expression::Index { left, index, .. } => {
+                self.expression(left);
+                self.expression(index);
+            }
+            Expression::Slice { left, start, end, .. } => {
+                self.expression(left);
+                for bound in [start, end].into_iter().flatten() {
+                    self.expression(bound);
+                }
+            }
+            Expression::Member { object, .. } => self.expression(object),
+            Expression::Propagate { value, .. } => self.expression(value),
+        }
+    }
+
+    fn expressions(&mut self, expressions: &'a [Rc<Expression>]) {
+        for expression in expressions {
+            self.expression(expression);
+        }
+    }
+}
+
+//////////////////// Tests //////////////////////
+
+#[cfg(test)]
+mod test {
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::resolver::Resolver;
+
+    fn resolve(resolver: &mut Resolver, input: &str) -> Vec<String> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert!(parser.errors().is_empty(), "parser has errors: {:?}", parser.errors());
+        resolver.resolve(&program).diagnostics.iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    fn assert_diagnostics(tests: Vec<(&str, Vec<&str>)>) {
+        for (input, expected) in tests {
+            assert_eq!(expected, resolve(&mut Resolver::new(), input), "wrong diagnostics for {input}");
+        }
+    }
+
+    #[test]
+    fn test_valid_programs() {
+        assert_diagnostics(vec![
+            ("var x = 1; x + len([x])", vec![]),
+            ("var f = func(n) { if (n == 0) { 0 } else { f(n - 1) } }; f(3)", vec![]),
+            ("var is_even = func(n) { is_odd(n - 1) }; var is_odd = func(n) { is_even(n - 1) }; 1", vec![]),
+            ("var counter = func() { var n = 0; func() { n = n + 1 } }; counter", vec![]),
+            ("for (var i = 0; i < 3; i = i + 1) { i } for (x in [1]) { } try { 1 } catch (e) { }", vec![]),
+            ("var x = 1; if (true) { var x = x + 1; x }", vec![]),
+            ("var f = func(_unused) { var _ignored = 1; 2 }; f", vec![]),
+            (r#"var name = "a"; "${name:>5}""#, vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_diagnostics(vec![
+            ("x + 1", vec!["Error: identifier not found: x at 1:1"]),
+            ("x = 1", vec!["Error: identifier not found: x at 1:3"]),
+            ("var x = x;", vec!["Error: identifier not found: x at 1:9"]),
+            ("var f = func() { missing };\nf", vec!["Error: identifier not found: missing at 1:18"]),
+            ("if (true) { var y = 1; y }\ny", vec!["Error: identifier not found: y at 2:1"]),
+            ("var x = 1;\nvar x = 2;", vec!["Error: 'x' is already declared in this scope at 2:5"]),
+            ("var f = func(a, a) { a };\nf", vec!["Error: 'a' is already declared in this scope at 1:17"]),
+            ("var f = func(a) { var a = 1; a };\nf", vec!["Error: 'a' is already declared in this scope at 1:23"]),
+            ("return 1", vec!["Error: return outside of a function at 1:1"]),
+            ("if (true) {\n  return\n}", vec!["Error: return outside of a function at 2:3"]),
+            (
+                "var f = func(n) { var a = n; b };\nf",
+                vec!["Warning: unused variable 'a' at 1:23", "Error: identifier not found: b at 1:30"],
+            ),
+        ]);
+    }
+
+    #[test]
+    fn test_warnings() {
+        assert_diagnostics(vec![
+            ("var f = func(a, b) { a };\nf", vec!["Warning: unused parameter 'b' at 1:17"]),
+            ("var f = func() { var x = 1; x = 2 };\nf", vec!["Warning: unused variable 'x' at 1:22"]),
+            ("if (true) { var y = 1 }", vec!["Warning: unused variable 'y' at 1:17"]),
+            ("var unused_global = 1", vec![]),
+        ]);
+    }
+
+    #[test]
+    fn test_globals_persist_between_programs() {
+        let mut resolver = Resolver::new();
+        assert!(resolve(&mut resolver, "var x = 1").is_empty());
+        assert!(resolve(&mut resolver, "var x = x + 1; x").is_empty());
+        assert_eq!(vec!["Error: identifier not found: y at 1:12"], resolve(&mut resolver, "var z = 1; y"));
+        assert_eq!(vec!["Error: identifier not found: z at 1:1"], resolve(&mut resolver, "z"));
+    }
+}
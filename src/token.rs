@@ -1,10 +1,23 @@
-#[derive(PartialEq, Debug)]
+#[derive(PartialEq, Debug, Clone)]
 pub struct Token {
     pub t_type: TokenType,
     pub literal: String, //TODO: use &'static str?
 }
 
-#[derive(PartialEq, Debug)]
+/// Position of a character in the source, both counted from 1.
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl std::fmt::Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(PartialEq, Debug, Clone, Copy)]
 pub enum TokenType {
     Illegal,
     EndOfFile,
@@ -12,6 +25,12 @@ pub enum TokenType {
     Identifier,
     Integer,
     Double,
+    String,
+    // "a ${x} b ${y:>4} c" is lexed as StringStart(a) x StringMiddle(b) y FormatSpec(>4) StringEnd(c)
+    StringStart,
+    StringMiddle,
+    StringEnd,
+    FormatSpec,
 
     // math operators
     Assign,
@@ -29,21 +48,40 @@ pub enum TokenType {
 
     Comma,
     Semicolon,
+    Colon,
     Bang,
+    Dot,
+    OptionalDot,
+    NullCoalescing,
+    Question,
 
     OpenParenthesis,
     CloseParenthesis,
     OpenBrace,
     CloseBrace,
+    OpenBracket,
+    CloseBracket,
 
     // keywords
     Function,
     Variable,
     True,
     False,
+    Null,
     If,
     Else,
     Return,
+    While,
+    For,
+    In,
+    Break,
+    Continue,
+    Try,
+    Catch,
+    Finally,
+    Throw,
+    Import,
+    Export,
 }
 
 //TODO: use std::fmt{...}
@@ -55,6 +93,11 @@ impl std::fmt::Display for TokenType {
             TokenType::Identifier => write!(f, "Identifier"),
             TokenType::Integer => write!(f, "Integer"),
             TokenType::Double => write!(f, "Double"),
+            TokenType::String => write!(f, "String"),
+            TokenType::StringStart => write!(f, "StringStart"),
+            TokenType::StringMiddle => write!(f, "StringMiddle"),
+            TokenType::StringEnd => write!(f, "StringEnd"),
+            TokenType::FormatSpec => write!(f, "FormatSpec"),
             TokenType::Assign => write!(f, "="),
             TokenType::Plus => write!(f, "+"),
             TokenType::Minus => write!(f, "-"),
@@ -68,32 +111,63 @@ impl std::fmt::Display for TokenType {
             TokenType::LessOrEqual => write!(f, "<="),
             TokenType::Comma => write!(f, ","),
             TokenType::Semicolon => write!(f, ";"),
+            TokenType::Colon => write!(f, ":"),
             TokenType::Bang => write!(f, "!"),
+            TokenType::Dot => write!(f, "."),
+            TokenType::OptionalDot => write!(f, "?."),
+            TokenType::NullCoalescing => write!(f, "??"),
+            TokenType::Question => write!(f, "?"),
             TokenType::OpenParenthesis => write!(f, "("),
             TokenType::CloseParenthesis => write!(f, ")"),
             TokenType::OpenBrace => write!(f, "{{"),
             TokenType::CloseBrace => write!(f, "}}"),
+            TokenType::OpenBracket => write!(f, "["),
+            TokenType::CloseBracket => write!(f, "]"),
             TokenType::Function => write!(f, "Function"),
             TokenType::Variable => write!(f, "Variable"),
             TokenType::True => write!(f, "True"),
             TokenType::False => write!(f, "False"),
+            TokenType::Null => write!(f, "Null"),
             TokenType::If => write!(f, "If"),
             TokenType::Else => write!(f, "Else"),
             TokenType::Return => write!(f, "Return"),
+            TokenType::While => write!(f, "While"),
+            TokenType::For => write!(f, "For"),
+            TokenType::In => write!(f, "In"),
+            TokenType::Break => write!(f, "Break"),
+            TokenType::Continue => write!(f, "Continue"),
+            TokenType::Try => write!(f, "Try"),
+            TokenType::Catch => write!(f, "Catch"),
+            TokenType::Finally => write!(f, "Finally"),
+            TokenType::Throw => write!(f, "Throw"),
+            TokenType::Import => write!(f, "Import"),
+            TokenType::Export => write!(f, "Export"),
         }
     }
 }
 
-pub fn lookup_identifier(identifier: &String) -> TokenType {
+pub fn lookup_identifier(identifier: &str) -> TokenType {
     //TODO use static str instead of str?
-    match identifier.as_str() {
+    match identifier {
         "func" => TokenType::Function,
         "var" => TokenType::Variable,
         "true" => TokenType::True,
         "false" => TokenType::False,
+        "null" => TokenType::Null,
         "if" => TokenType::If,
         "else" => TokenType::Else,
         "return" => TokenType::Return,
+        "while" => TokenType::While,
+        "for" => TokenType::For,
+        "in" => TokenType::In,
+        "break" => TokenType::Break,
+        "continue" => TokenType::Continue,
+        "try" => TokenType::Try,
+        "catch" => TokenType::Catch,
+        "finally" => TokenType::Finally,
+        "throw" => TokenType::Throw,
+        "import" => TokenType::Import,
+        "export" => TokenType::Export,
         _ => TokenType::Identifier
     }
 }
\ No newline at end of file
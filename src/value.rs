@@ -0,0 +1,212 @@
+//! Conversions between Rust values and script values, used to pass values to and from the host.
+
+use std::collections::{BTreeMap, HashMap};
+use crate::object::{ErrorKind, Hash, Object, RuntimeError};
+
+/// A Rust value that can be given to a script.
+pub trait IntoValue {
+    fn into_value(self) -> Object;
+}
+
+/// A Rust value that can be taken from a script, failing with a `TypeError` for values of another type.
+pub trait FromValue: Sized {
+    fn from_value(value: Object) -> Result<Self, RuntimeError>;
+}
+
+fn type_error(expected: &str, value: &Object) -> RuntimeError {
+    RuntimeError::new(ErrorKind::TypeError, format!("expected {expected}, got {}", value.type_name()))
+}
+
+impl IntoValue for Object {
+    fn into_value(self) -> Object {
+        self
+    }
+}
+
+impl FromValue for Object {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        Ok(value)
+    }
+}
+
+impl IntoValue for () {
+    fn into_value(self) -> Object {
+        Object::Null
+    }
+}
+
+/// Accepts any value, for results the host is not interested in.
+impl FromValue for () {
+    fn from_value(_: Object) -> Result<Self, RuntimeError> {
+        Ok(())
+    }
+}
+
+impl IntoValue for bool {
+    fn into_value(self) -> Object {
+        Object::Boolean(self)
+    }
+}
+
+impl FromValue for bool {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Boolean(value) => Ok(value),
+            other => Err(type_error("BOOLEAN", &other)),
+        }
+    }
+}
+
+impl IntoValue for i64 {
+    fn into_value(self) -> Object {
+        Object::Integer(self)
+    }
+}
+
+impl FromValue for i64 {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Integer(value) => Ok(value),
+            Object::BigInteger(value) => {
+                Err(RuntimeError::new(ErrorKind::ValueError, format!("integer out of range: {value}")))
+            }
+            other => Err(type_error("INTEGER", &other)),
+        }
+    }
+}
+
+/// Smaller integer types, converted through `i64`.
+macro_rules! integer_values {
+    ($($integer:ty),*) => {$(
+        impl IntoValue for $integer {
+            fn into_value(self) -> Object {
+                Object::Integer(self.into())
+            }
+        }
+
+        impl FromValue for $integer {
+            fn from_value(value: Object) -> Result<Self, RuntimeError> {
+                let value = i64::from_value(value)?;
+                <$integer>::try_from(value).map_err(|_| {
+                    RuntimeError::new(ErrorKind::ValueError, format!("integer out of range: {value}"))
+                })
+            }
+        }
+    )*};
+}
+
+integer_values!(i32, u32);
+
+impl IntoValue for f64 {
+    fn into_value(self) -> Object {
+        Object::Double(self)
+    }
+}
+
+/// Integers are accepted too, like arithmetic mixing both does.
+impl FromValue for f64 {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Double(value) => Ok(value),
+            Object::Integer(value) => Ok(value as f64),
+            Object::BigInteger(value) => Ok(value.to_f64()),
+            other => Err(type_error("DOUBLE", &other)),
+        }
+    }
+}
+
+impl IntoValue for String {
+    fn into_value(self) -> Object {
+        Object::String(self)
+    }
+}
+
+impl IntoValue for &str {
+    fn into_value(self) -> Object {
+        Object::String(self.to_string())
+    }
+}
+
+impl FromValue for String {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::String(value) => Ok(value),
+            other => Err(type_error("STRING", &other)),
+        }
+    }
+}
+
+/// `None` is null.
+impl<T: IntoValue> IntoValue for Option<T> {
+    fn into_value(self) -> Object {
+        self.map_or(Object::Null, T::into_value)
+    }
+}
+
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Null => Ok(None),
+            other => T::from_value(other).map(Some),
+        }
+    }
+}
+
+impl<T: IntoValue> IntoValue for Vec<T> {
+    fn into_value(self) -> Object {
+        Object::new_array(self.into_iter().map(T::into_value).collect())
+    }
+}
+
+/// Copies the elements, the array and the vector do not share changes.
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        match value {
+            Object::Array(elements) => elements.borrow().iter().cloned().map(T::from_value).collect(),
+            other => Err(type_error("ARRAY", &other)),
+        }
+    }
+}
+
+fn into_hash<T: IntoValue>(entries: impl IntoIterator<Item = (String, T)>) -> Object {
+    let mut hash = Hash::new();
+    for (key, value) in entries {
+        hash.insert(Object::String(key), value.into_value()).expect("strings are valid hash keys");
+    }
+    Object::new_hash(hash)
+}
+
+fn from_hash<T: FromValue, M: FromIterator<(String, T)>>(value: Object) -> Result<M, RuntimeError> {
+    match value {
+        Object::Hash(hash) => hash.borrow().entries().iter().cloned()
+            .map(|(key, value)| Ok((String::from_value(key)?, T::from_value(value)?)))
+            .collect(),
+        other => Err(type_error("HASH", &other)),
+    }
+}
+
+/// The keys are inserted in the order the map iterates them, which is unspecified.
+impl<T: IntoValue> IntoValue for HashMap<String, T> {
+    fn into_value(self) -> Object {
+        into_hash(self)
+    }
+}
+
+impl<T: FromValue> FromValue for HashMap<String, T> {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        from_hash(value)
+    }
+}
+
+/// The keys are inserted in ascending order.
+impl<T: IntoValue> IntoValue for BTreeMap<String, T> {
+    fn into_value(self) -> Object {
+        into_hash(self)
+    }
+}
+
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    fn from_value(value: Object) -> Result<Self, RuntimeError> {
+        from_hash(value)
+    }
+}